@@ -36,9 +36,17 @@ fn check_library_found(
     return None;
 }
 
+/// Check if rpath is disabled by `DFTD3_NO_RPATH`; empty value and "0" do not disable rpath.
+fn rpath_disabled() -> bool {
+    match std::env::var("DFTD3_NO_RPATH") {
+        Ok(value) => !value.is_empty() && value != "0",
+        Err(_) => false,
+    }
+}
+
 fn main() {
     // search dirs
-    for key in ["DFTD3_DIR", "REST_EXT_DIR"].iter() {
+    for key in ["DFTD3_DIR", "REST_EXT_DIR", "DFTD3_NO_RPATH"].iter() {
         println!("cargo:rerun-if-env-changed={}", key);
     }
     let lib_paths = generate_link_search_paths(&[
@@ -47,32 +55,56 @@ fn main() {
         std::env::var("LD_LIBRARY_PATH"),
     ]);
     // static linking or anyway
-    if cfg!(feature = "static") {
-        if let Some(path) = check_library_found("s-dftd3", &lib_paths, &["a".to_string()]) {
-            let path = std::fs::canonicalize(path).unwrap();
-            let path = path.parent().unwrap().display();
-            println!("cargo:rustc-link-search=native={}", path);
-        } else {
-            let dst = cmake::Config::new("external_deps").build();
-            println!("cargo:rustc-link-search=native={}/lib", dst.display());
-        }
+    let lib_dir = if cfg!(feature = "static") {
+        let lib_dir =
+            if let Some(path) = check_library_found("s-dftd3", &lib_paths, &["a".to_string()]) {
+                let path = std::fs::canonicalize(path).unwrap();
+                path.parent().unwrap().display().to_string()
+            } else {
+                let dst = cmake::Config::new("external_deps").build();
+                format!("{}/lib", dst.display())
+            };
+        println!("cargo:rustc-link-search=native={}", lib_dir);
         println!("cargo:rustc-link-lib=static=s-dftd3");
         println!("cargo:rustc-link-lib=static=mctc-lib");
         println!("cargo:rustc-link-lib=gomp");
         println!("cargo:rustc-link-lib=gfortran");
+        lib_dir
     } else {
-        if let Some(path) = check_library_found("s-dftd3", &lib_paths, &["so".to_string()]) {
+        let lib_dir =
+            if let Some(path) = check_library_found("s-dftd3", &lib_paths, &["so".to_string()]) {
+                let path = std::fs::canonicalize(path).unwrap();
+                let lib_dir = path.parent().unwrap().display().to_string();
+                println!("cargo:rustc-link-search=native={}", lib_dir);
+                println!("cargo:rustc-link-lib=s-dftd3");
+                lib_dir
+            } else {
+                let dst = cmake::Config::new("external_deps")
+                    .define("BUILD_SHARED_LIBS", "1")
+                    .build();
+                let lib_dir = format!("{}/lib", dst.display());
+                println!("cargo:rustc-link-search=native={}", lib_dir);
+                println!("cargo:rustc-link-lib=mctc-lib");
+                println!("cargo:rustc-link-lib=s-dftd3");
+                lib_dir
+            };
+        // mctc-lib may be installed in another directory than s-dftd3
+        let mut rpath_dirs = vec![lib_dir.clone()];
+        if let Some(path) = check_library_found("mctc-lib", &lib_paths, &["so".to_string()]) {
             let path = std::fs::canonicalize(path).unwrap();
-            let path = path.parent().unwrap().display();
-            println!("cargo:rustc-link-search=native={}", path);
-            println!("cargo:rustc-link-lib=s-dftd3");
-        } else {
-            let dst = cmake::Config::new("external_deps")
-                .define("BUILD_SHARED_LIBS", "1")
-                .build();
-            println!("cargo:rustc-link-search=native={}/lib", dst.display());
-            println!("cargo:rustc-link-lib=mctc-lib");
-            println!("cargo:rustc-link-lib=s-dftd3");
+            let mctc_dir = path.parent().unwrap().display().to_string();
+            if !rpath_dirs.contains(&mctc_dir) {
+                rpath_dirs.push(mctc_dir);
+            }
         }
-    }
+        // embed rpath, so tests and binaries of this crate run without LD_LIBRARY_PATH
+        if !rpath_disabled() && !cfg!(feature = "no_rpath") {
+            for dir in rpath_dirs {
+                println!("cargo:rustc-link-arg=-Wl,-rpath,{}", dir);
+            }
+        }
+        lib_dir
+    };
+    // export library directory to dependents (as `DEP_S_DFTD3_LIB_DIR` in their build scripts)
+    println!("cargo:lib_dir={}", lib_dir);
 }
@@ -0,0 +1,166 @@
+//! Command line interface of the Rust wrapper of s-dftd3.
+
+use rest_dftd3::prelude::*;
+use rest_dftd3::server::{IpiAddress, IpiDriver};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+const USAGE: &str = "\
+Usage: dftd3-cli <command> [options]
+
+Commands:
+  ipi    Run as i-PI client driver (energy, forces and virial of dispersion)
+  serve  Run line-delimited JSON service on stdin/stdout (requires feature json)
+  trajectory
+         Evaluate dispersion energy, maximum force and pressure of each frame of (extended) XYZ
+         trajectory (energy in Hartree, force in Hartree/Bohr, pressure in GPa)
+
+Options of damping parameters:
+  --method <name>      method of damping parameters (required)
+  --damping <kind>     d3bj (default), d3zero, d3bjm, d3zerom or d3op
+  --atm                include three-body (ATM) dispersion
+
+Options of ipi:
+  --symbols <list>     comma-separated element symbols in the order of atoms of the server
+  --unix <name>        UNIX socket name (/tmp/ipi_<name>) or path
+  --tcp <host:port>    TCP socket address
+  --periodic           use cell from server as lattice
+
+Options of serve:
+  --unix <path>        serve clients on UNIX socket instead of stdin/stdout
+
+Options of trajectory:
+  --input <file>       trajectory file (positions and lattice in Angstrom); stdin if not given
+  --output <file>      output file; stdout if not given
+  --format <kind>      csv (default) or json
+  --nthreads <n>       number of threads (default 1)
+  --chunk <n>          number of frames per parallel chunk (default 256)
+";
+
+/// Parse `--key value` options and `--flag` switches.
+fn parse_options(args: &[String], flags: &[&str]) -> Result<HashMap<String, String>, String> {
+    let mut options = HashMap::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let key = arg
+            .strip_prefix("--")
+            .ok_or_else(|| format!("Unexpected argument {}", arg))?;
+        let value = match flags.contains(&key) {
+            true => String::new(),
+            false => iter
+                .next()
+                .ok_or_else(|| format!("Missing value of option --{}", key))?
+                .clone(),
+        };
+        options.insert(key.to_string(), value);
+    }
+    Ok(options)
+}
+
+fn load_param(options: &HashMap<String, String>) -> Result<DFTD3Param, String> {
+    let method = options.get("method").ok_or("Missing option --method")?;
+    let damping = options.get("damping").map_or("d3bj", |x| x.as_str());
+    DFTD3Param::load_damping_f(damping, method, options.contains_key("atm"))
+        .map_err(|err| err.get_message())
+}
+
+fn parse_symbols(symbols: &str) -> Result<Vec<usize>, String> {
+    symbols
+        .split(',')
+        .map(|x| element_number(x).ok_or_else(|| format!("Unknown element symbol {}", x)))
+        .collect()
+}
+
+fn run_ipi(args: &[String]) -> Result<(), String> {
+    let options = parse_options(args, &["atm", "periodic"])?;
+    let param = load_param(&options)?;
+    let numbers = parse_symbols(options.get("symbols").ok_or("Missing option --symbols")?)?;
+    let address = match (options.get("unix"), options.get("tcp")) {
+        (Some(name), None) => IpiAddress::Unix(name.clone()),
+        (None, Some(address)) => IpiAddress::Tcp(address.clone()),
+        _ => return Err("Exactly one of --unix and --tcp is required".to_string()),
+    };
+    let mut driver = IpiDriver::new(&numbers, param, options.contains_key("periodic"));
+    let nsteps = driver
+        .connect_f(&address)
+        .map_err(|err| err.get_message())?;
+    eprintln!("i-PI server sent EXIT after {} steps", nsteps);
+    Ok(())
+}
+
+fn run_trajectory(args: &[String]) -> Result<(), String> {
+    let options = parse_options(args, &["atm"])?;
+    let param = load_param(&options)?;
+    let format = match options.get("format").map_or("csv", |x| x.as_str()) {
+        "csv" => TrajectoryFormat::CSV,
+        "json" => TrajectoryFormat::JSON,
+        format => return Err(format!("Unknown output format {}", format)),
+    };
+    let parse_count = |key: &str, default: usize| match options.get(key) {
+        Some(value) => value
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid value of option --{}", key)),
+        None => Ok(default),
+    };
+    let trajectory_options = TrajectoryOptions {
+        nthreads: parse_count("nthreads", 1)?,
+        chunk_size: parse_count("chunk", 256)?,
+        ..Default::default()
+    };
+    let reader: Box<dyn BufRead> = match options.get("input") {
+        Some(path) => Box::new(BufReader::new(
+            File::open(path).map_err(|err| format!("Cannot open {}: {}", path, err))?,
+        )),
+        None => Box::new(std::io::stdin().lock()),
+    };
+    let writer: Box<dyn Write> = match options.get("output") {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).map_err(|err| format!("Cannot create {}: {}", path, err))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let mut writer = TrajectoryWriter::new(writer, format);
+    evaluate_trajectory_f(reader, &param, &trajectory_options, |result| {
+        writer.write_f(&result)
+    })
+    .and_then(|_| writer.finish_f().map(|_| ()))
+    .map_err(|err| err.get_message())
+}
+
+#[cfg(feature = "json")]
+fn run_serve(args: &[String]) -> Result<(), String> {
+    let options = parse_options(args, &[])?;
+    let mut service = rest_dftd3::service::DFTD3Service::new();
+    let result = match options.get("unix") {
+        #[cfg(unix)]
+        Some(path) => service.run_unix_f(path),
+        #[cfg(not(unix))]
+        Some(_) => return Err("UNIX sockets are not supported on this platform".to_string()),
+        None => service.run_stdio_f(),
+    };
+    result.map_err(|err| err.get_message())
+}
+
+#[cfg(not(feature = "json"))]
+fn run_serve(_args: &[String]) -> Result<(), String> {
+    Err("Command serve requires feature json".to_string())
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let result = match args.first().map(|x| x.as_str()) {
+        Some("ipi") => run_ipi(&args[1..]),
+        Some("serve") => run_serve(&args[1..]),
+        Some("trajectory") => run_trajectory(&args[1..]),
+        Some("-h") | Some("--help") => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(format!("Missing or unknown command\n\n{}", USAGE)),
+    };
+    if let Err(message) = result {
+        eprintln!("Error: {}", message);
+        std::process::exit(1);
+    }
+}
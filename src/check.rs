@@ -0,0 +1,105 @@
+//! Consistency check of analytic derivatives against finite differences.
+
+use crate::hessian::apply_strain;
+use crate::prelude::*;
+
+/// Displacement step of atomic positions (in Bohr) for derivative check
+pub const CHECK_POSITION_STEP: f64 = 1.0e-4;
+
+/// Strain step for derivative check
+pub const CHECK_STRAIN_STEP: f64 = 1.0e-5;
+
+/// Result of derivative check
+///
+/// Errors are analytic values minus finite-difference values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivativeCheck {
+    /// error of gradient [natoms][3]
+    pub gradient_error: Vec<f64>,
+    /// error of sigma [3][3]
+    pub sigma_error: Vec<f64>,
+}
+
+impl DerivativeCheck {
+    /// Maximum absolute error of gradient
+    pub fn max_gradient_error(&self) -> f64 {
+        self.gradient_error
+            .iter()
+            .fold(0.0, |acc, x| acc.max(x.abs()))
+    }
+
+    /// Maximum absolute error of sigma
+    pub fn max_sigma_error(&self) -> f64 {
+        self.sigma_error.iter().fold(0.0, |acc, x| acc.max(x.abs()))
+    }
+}
+
+/// Compare analytic gradient and sigma against finite differences of energy (failable)
+///
+/// Gradient is compared to central differences of energy with respect to atomic positions
+/// (step [`CHECK_POSITION_STEP`]). Sigma is compared to central differences of energy with respect
+/// to homogeneous strain `r' = (1 + eps) r` of positions and lattice vectors (step
+/// [`CHECK_STRAIN_STEP`]); for molecules only positions are strained.
+///
+/// Displacements are performed by [`DFTD3Structure::update_f`]; the original positions and lattice
+/// are restored afterwards.
+pub fn check_derivatives_f(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+) -> Result<DerivativeCheck, DFTD3Error> {
+    let positions = structure.get_positions();
+    let lattice = structure.get_lattice();
+    let (_, gradient, sigma) = get_dispersion_f(structure, model, param, true, true)?;
+    let (gradient, sigma) = (gradient.unwrap(), sigma.unwrap());
+
+    let energy_at = |positions: &[f64], lattice: Option<&[f64]>| {
+        structure.update_f(positions, lattice)?;
+        Ok(get_dispersion_f(structure, model, param, false, false)?.0)
+    };
+    let numerical = || -> Result<(Vec<f64>, Vec<f64>), DFTD3Error> {
+        let h = CHECK_POSITION_STEP;
+        let mut numerical_gradient = vec![0.0; positions.len()];
+        for (i, value) in numerical_gradient.iter_mut().enumerate() {
+            let mut displaced = positions.clone();
+            displaced[i] = positions[i] + h;
+            let energy_p = energy_at(&displaced, None)?;
+            displaced[i] = positions[i] - h;
+            let energy_m = energy_at(&displaced, None)?;
+            *value = (energy_p - energy_m) / (2.0 * h);
+        }
+        let h = CHECK_STRAIN_STEP;
+        let mut numerical_sigma = vec![0.0; 9];
+        for (ab, value) in numerical_sigma.iter_mut().enumerate() {
+            let mut eps = [0.0; 9];
+            eps[ab] = h;
+            let (strained, strained_lattice) = apply_strain(&positions, lattice.as_deref(), &eps);
+            let energy_p = energy_at(&strained, strained_lattice.as_deref())?;
+            eps[ab] = -h;
+            let (strained, strained_lattice) = apply_strain(&positions, lattice.as_deref(), &eps);
+            let energy_m = energy_at(&strained, strained_lattice.as_deref())?;
+            *value = (energy_p - energy_m) / (2.0 * h);
+        }
+        Ok((numerical_gradient, numerical_sigma))
+    };
+    let result = numerical();
+    structure.update_f(&positions, lattice.as_deref())?;
+    let (numerical_gradient, numerical_sigma) = result?;
+
+    let difference = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(x, y)| x - y).collect();
+    Ok(DerivativeCheck {
+        gradient_error: difference(&gradient, &numerical_gradient),
+        sigma_error: difference(&sigma, &numerical_sigma),
+    })
+}
+
+/// Compare analytic gradient and sigma against finite differences of energy
+///
+/// See also [`check_derivatives_f`].
+pub fn check_derivatives(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+) -> DerivativeCheck {
+    check_derivatives_f(structure, model, param).unwrap()
+}
@@ -0,0 +1,164 @@
+//! Realspace cutoffs of the D3 model.
+
+use crate::prelude::*;
+
+/// Realspace cutoffs (in Bohr) of the D3 model
+///
+/// Default values are those of s-dftd3 (two-body 60, three-body 40 and coordination number 40
+/// Bohr). Cutoffs matter for periodic systems and large molecules.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RealspaceCutoff {
+    /// cutoff of two-body dispersion
+    pub disp2: f64,
+    /// cutoff of three-body dispersion
+    pub disp3: f64,
+    /// cutoff of coordination numbers
+    pub cn: f64,
+}
+
+impl Default for RealspaceCutoff {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl RealspaceCutoff {
+    /// Library defaults
+    pub const DEFAULT: RealspaceCutoff = RealspaceCutoff {
+        disp2: 60.0,
+        disp3: 40.0,
+        cn: 40.0,
+    };
+
+    /// Larger cutoffs for tightly converged energies of periodic systems
+    pub const TIGHT: RealspaceCutoff = RealspaceCutoff {
+        disp2: 100.0,
+        disp3: 60.0,
+        cn: 60.0,
+    };
+
+    /// Smaller cutoffs for cheap screening calculations
+    pub const LOOSE: RealspaceCutoff = RealspaceCutoff {
+        disp2: 40.0,
+        disp3: 25.0,
+        cn: 25.0,
+    };
+
+    /// Create realspace cutoffs (quantities in Bohr) (failable)
+    ///
+    /// See [`RealspaceCutoff::validate_f`] for validation.
+    pub fn new_f(disp2: f64, disp3: f64, cn: f64) -> Result<Self, DFTD3Error> {
+        let cutoff = Self { disp2, disp3, cn };
+        cutoff.validate_f()?;
+        Ok(cutoff)
+    }
+
+    /// Create realspace cutoffs (quantities in Bohr)
+    pub fn new(disp2: f64, disp3: f64, cn: f64) -> Self {
+        Self::new_f(disp2, disp3, cn).unwrap()
+    }
+
+    /// Check that cutoffs are positive and finite, and three-body cutoff does not exceed two-body
+    /// cutoff (failable)
+    pub fn validate_f(&self) -> Result<(), DFTD3Error> {
+        for (name, value) in [
+            ("disp2", self.disp2),
+            ("disp3", self.disp3),
+            ("cn", self.cn),
+        ] {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(DFTD3Error::Rust(format!(
+                    "Invalid realspace cutoff {}, expected positive value, got {}",
+                    name, value
+                )));
+            }
+        }
+        if self.disp3 > self.disp2 {
+            return Err(DFTD3Error::Rust(format!(
+                "Invalid realspace cutoff disp3, expected at most disp2 ({}), got {}",
+                self.disp2, self.disp3
+            )));
+        }
+        Ok(())
+    }
+
+    /// Cutoffs with all values multiplied by `factor`
+    pub fn scaled(&self, factor: f64) -> Self {
+        Self {
+            disp2: self.disp2 * factor,
+            disp3: self.disp3 * factor,
+            cn: self.cn * factor,
+        }
+    }
+}
+
+/// Evaluate dispersion energy with each of given realspace cutoffs (failable)
+///
+/// This is intended for convergence tests of periodic structures, e.g. over
+/// `[1.0, 1.25, 1.5, 2.0].map(|x| RealspaceCutoff::DEFAULT.scaled(x))`. Cutoffs are applied to the
+/// given model in turn; the originally applied cutoffs are restored afterwards.
+pub fn cutoff_convergence_f(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    cutoffs: &[RealspaceCutoff],
+) -> Result<Vec<f64>, DFTD3Error> {
+    let original = model.get_realspace_cutoff();
+    let energies = cutoffs
+        .iter()
+        .map(|cutoff| {
+            model.apply_realspace_cutoff_f(cutoff)?;
+            Ok(get_dispersion_f(structure, model, param, false, false)?.0)
+        })
+        .collect::<Result<Vec<f64>, DFTD3Error>>();
+    model.apply_realspace_cutoff_f(&original)?;
+    energies
+}
+
+/// Evaluate dispersion energy with each of given realspace cutoffs
+///
+/// See also [`cutoff_convergence_f`].
+pub fn cutoff_convergence(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    cutoffs: &[RealspaceCutoff],
+) -> Vec<f64> {
+    cutoff_convergence_f(structure, model, param, cutoffs).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_realspace_cutoff() {
+        assert!(RealspaceCutoff::new_f(40.0, 60.0, 40.0).is_err());
+        assert!(RealspaceCutoff::new_f(60.0, 40.0, -1.0).is_err());
+        assert!(RealspaceCutoff::new_f(60.0, f64::NAN, 40.0).is_err());
+
+        let positions = [0.0, 0.0, 0.0, 1.9, 1.9, 1.9];
+        let lattice = [7.6, 0.0, 0.0, 0.0, 7.6, 0.0, 0.0, 0.0, 7.6];
+        let periodic = [true, true, true];
+        let structure =
+            DFTD3Structure::new(2, &[11, 17], &positions, Some(&lattice), Some(&periodic));
+        let model = DFTD3Model::new(&structure);
+        let param = DFTD3Param::load_rational_damping("PBE", true);
+        assert_eq!(model.get_realspace_cutoff(), RealspaceCutoff::DEFAULT);
+
+        model.set_realspace_cutoff(50.0, 30.0, 30.0);
+        assert_eq!(
+            model.get_realspace_cutoff(),
+            RealspaceCutoff::new(50.0, 30.0, 30.0)
+        );
+        // invalid cutoffs are rejected and do not change applied values
+        assert!(model.set_realspace_cutoff_f(30.0, 50.0, 30.0).is_err());
+        assert_eq!(model.get_realspace_cutoff().disp2, 50.0);
+
+        let cutoffs = [1.0, 1.5, 2.0].map(|x| RealspaceCutoff::LOOSE.scaled(x));
+        let energies = cutoff_convergence(&structure, &model, &param, &cutoffs);
+        assert_eq!(model.get_realspace_cutoff().disp2, 50.0);
+        // energy converges with increasing cutoffs
+        assert!((energies[2] - energies[1]).abs() < (energies[1] - energies[0]).abs());
+    }
+}
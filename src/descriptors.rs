@@ -0,0 +1,328 @@
+//! Coordination numbers and dispersion coefficients of the D3 model.
+//!
+//! The C API of s-dftd3 does not expose these model internals, so they are obtained on the Rust
+//! side:
+//!
+//! - Coordination numbers are evaluated with the D3 counting function
+//!   `1 / (1 + exp(-k1 ((Rcov_i + Rcov_j) / r_ij - 1)))`, with `k1 = 16` and the D3 covalent
+//!   radii ([`COVALENT_RADII_D3`], Pyykko and Atsumi with metals decreased by 10%) scaled by 4/3,
+//!   as in s-dftd3.
+//! - Pairwise C6 and C8 coefficients are extracted from pairwise energies of the library evaluated
+//!   with undamped zero damping (`s9 = 0`, vanishing `rs6` and `rs8`), where the two-body energy of
+//!   pair `(i, j)` is exactly `-C6_ij / r_ij^6` (or `-C8_ij / r_ij^8`). So these are the
+//!   coefficients interpolated by the library at the coordination numbers of given geometry.
+
+use crate::prelude::*;
+use crate::stress::inverse;
+
+/// Steepness of the D3 coordination number counting function
+pub const CN_STEEPNESS: f64 = 16.0;
+
+/// Scaling of damping radius for extraction of dispersion coefficients; damping vanishes at any
+/// relevant interatomic distance.
+const UNDAMPED_RADIUS_SCALE: f64 = 1.0e-3;
+
+/// Covalent radius (in Bohr) of D3 coordination number of atomic number
+pub fn covalent_radius_d3(number: usize) -> Option<f64> {
+    match number {
+        1..=118 => Some(4.0 / 3.0 * COVALENT_RADII_D3[number] / BOHR_TO_ANGSTROM),
+        _ => None,
+    }
+}
+
+/// Whether structure is periodic in any direction
+fn is_periodic(structure: &DFTD3Structure) -> bool {
+    structure.get_lattice().is_some()
+        && structure
+            .get_periodic()
+            .is_none_or(|periodic| periodic.iter().any(|&x| x))
+}
+
+/// Lattice translations [ntrans][3] covering all pairs within cutoff, including origin.
+fn lattice_translations(
+    structure: &DFTD3Structure,
+    cutoff: f64,
+) -> Result<Vec<[f64; 3]>, DFTD3Error> {
+    let lattice = match structure.get_lattice() {
+        Some(lattice) if is_periodic(structure) => lattice,
+        _ => return Ok(vec![[0.0; 3]]),
+    };
+    let periodic = structure.get_periodic().unwrap_or(&[true, true, true]);
+    let inv =
+        inverse(&lattice).ok_or_else(|| DFTD3Error::Rust("Lattice is singular".to_string()))?;
+    // number of images along each lattice vector from spacing of lattice planes
+    let reps = (0..3)
+        .map(|k| match periodic[k] {
+            true => {
+                let norm = (0..3).map(|j| inv[3 * j + k].powi(2)).sum::<f64>().sqrt();
+                (cutoff * norm).ceil() as i64 + 1
+            }
+            false => 0,
+        })
+        .collect::<Vec<i64>>();
+    let mut translations = vec![];
+    for n0 in -reps[0]..=reps[0] {
+        for n1 in -reps[1]..=reps[1] {
+            for n2 in -reps[2]..=reps[2] {
+                let n = [n0 as f64, n1 as f64, n2 as f64];
+                translations
+                    .push([0, 1, 2].map(|a| (0..3).map(|k| n[k] * lattice[3 * k + a]).sum()));
+            }
+        }
+    }
+    Ok(translations)
+}
+
+/// Evaluate D3 coordination numbers [natoms] with given realspace cutoff (in Bohr) (failable)
+pub fn get_coordination_numbers_f(
+    structure: &DFTD3Structure,
+    cutoff: f64,
+) -> Result<Vec<f64>, DFTD3Error> {
+    let numbers = structure.get_numbers();
+    let positions = structure.get_positions();
+    let rcov = numbers
+        .iter()
+        .map(|&x| {
+            covalent_radius_d3(x)
+                .ok_or_else(|| DFTD3Error::Rust(format!("Invalid atomic number {}", x)))
+        })
+        .collect::<Result<Vec<f64>, DFTD3Error>>()?;
+    let translations = lattice_translations(structure, cutoff)?;
+    let cutoff2 = cutoff * cutoff;
+    let natoms = structure.get_natoms();
+    let mut cn = vec![0.0; natoms];
+    for i in 0..natoms {
+        for j in 0..natoms {
+            for t in &translations {
+                let r2 = (0..3)
+                    .map(|k| (positions[3 * i + k] - positions[3 * j + k] - t[k]).powi(2))
+                    .sum::<f64>();
+                if r2 > cutoff2 || r2 < f64::EPSILON {
+                    continue;
+                }
+                let rc = rcov[i] + rcov[j];
+                cn[i] += 1.0 / (1.0 + (-CN_STEEPNESS * (rc / r2.sqrt() - 1.0)).exp());
+            }
+        }
+    }
+    Ok(cn)
+}
+
+/// Evaluate D3 coordination numbers [natoms] with given realspace cutoff (in Bohr)
+pub fn get_coordination_numbers(structure: &DFTD3Structure, cutoff: f64) -> Vec<f64> {
+    get_coordination_numbers_f(structure, cutoff).unwrap()
+}
+
+/// Pairwise dispersion coefficients of the D3 model at given geometry
+#[derive(Debug, Clone, PartialEq)]
+pub struct DFTD3Coefficients {
+    natoms: usize,
+    /// pairwise C6 coefficients [natoms][natoms] (in Hartree Bohr^6)
+    pub c6: Vec<f64>,
+    /// pairwise C8 coefficients [natoms][natoms] (in Hartree Bohr^8)
+    pub c8: Vec<f64>,
+}
+
+impl DFTD3Coefficients {
+    /// Get number of atoms
+    pub fn get_natoms(&self) -> usize {
+        self.natoms
+    }
+
+    /// Effective atomic C6 coefficients [natoms] (in Hartree Bohr^6) (failable)
+    ///
+    /// Atomic coefficients are the least-squares fit of `ln C6_ij = (ln C6_i + ln C6_j) / 2` over
+    /// all pairs, i.e. those whose geometric-mean combination best reproduces the pairwise
+    /// coefficients. Their square root is proportional to a static polarizability-like quantity.
+    /// All pairwise C6 must be positive (within realspace cutoff).
+    pub fn atomic_c6_f(&self) -> Result<Vec<f64>, DFTD3Error> {
+        let n = self.natoms;
+        if n < 2 {
+            return Err(DFTD3Error::Rust(
+                "Atomic C6 requires at least two atoms".to_string(),
+            ));
+        }
+        let mut rhs = vec![0.0; n];
+        for (i, value) in rhs.iter_mut().enumerate() {
+            for j in (0..n).filter(|&j| j != i) {
+                let c6 = self.c6[i * n + j];
+                if c6 <= 0.0 {
+                    return Err(DFTD3Error::Rust(format!(
+                        "Atomic C6 requires positive pairwise C6, got {} for pair ({}, {})",
+                        c6, i, j
+                    )));
+                }
+                *value += c6.ln();
+            }
+        }
+        // normal equations of x_i + x_j = ln C6_ij: (n - 2) x_i + sum(x) = rhs_i
+        let sum = rhs.iter().sum::<f64>() / (2 * n - 2) as f64;
+        let log_c6 = match n {
+            2 => vec![sum; 2],
+            _ => rhs.iter().map(|b| (b - sum) / (n - 2) as f64).collect(),
+        };
+        Ok(log_c6.into_iter().map(|x| (2.0 * x).exp()).collect())
+    }
+
+    /// Effective atomic C6 coefficients [natoms] (in Hartree Bohr^6)
+    pub fn atomic_c6(&self) -> Vec<f64> {
+        self.atomic_c6_f().unwrap()
+    }
+}
+
+impl DFTD3Model {
+    /// Evaluate D3 coordination numbers [natoms] of structure (failable)
+    ///
+    /// See [`get_coordination_numbers_f`]; realspace cutoff is that of coordination numbers applied
+    /// to this model.
+    pub fn get_coordination_numbers_f(
+        &self,
+        structure: &DFTD3Structure,
+    ) -> Result<Vec<f64>, DFTD3Error> {
+        get_coordination_numbers_f(structure, self.get_realspace_cutoff().cn)
+    }
+
+    /// Evaluate D3 coordination numbers [natoms] of structure
+    pub fn get_coordination_numbers(&self, structure: &DFTD3Structure) -> Vec<f64> {
+        self.get_coordination_numbers_f(structure).unwrap()
+    }
+
+    /// Evaluate pairwise C6 and C8 coefficients of molecular structure (failable)
+    ///
+    /// Coefficients are extracted from undamped pairwise energies of this model (see module
+    /// documentation). Only molecular (non-periodic) structures are supported; diagonal elements
+    /// and pairs beyond the two-body realspace cutoff are zero.
+    pub fn get_dispersion_coefficients_f(
+        &self,
+        structure: &DFTD3Structure,
+    ) -> Result<DFTD3Coefficients, DFTD3Error> {
+        if is_periodic(structure) {
+            return Err(DFTD3Error::Rust(
+                "Dispersion coefficients are only available for molecular structures".to_string(),
+            ));
+        }
+        let scale = UNDAMPED_RADIUS_SCALE;
+        let param6 = DFTD3Param::new_zero_damping_f(1.0, 0.0, 0.0, scale, scale, 14.0)?;
+        let param8 = DFTD3Param::new_zero_damping_f(0.0, 1.0, 0.0, scale, scale, 14.0)?;
+        let (pair6, _) = get_pairwise_dispersion_f(structure, self, &param6)?;
+        let (pair8, _) = get_pairwise_dispersion_f(structure, self, &param8)?;
+
+        let n = structure.get_natoms();
+        let positions = structure.get_positions();
+        let mut c6 = vec![0.0; n * n];
+        let mut c8 = vec![0.0; n * n];
+        for i in 0..n {
+            for j in (0..n).filter(|&j| j != i) {
+                let r2 = (0..3)
+                    .map(|k| (positions[3 * i + k] - positions[3 * j + k]).powi(2))
+                    .sum::<f64>();
+                c6[i * n + j] = -(pair6[i * n + j] + pair6[j * n + i]) * r2.powi(3);
+                c8[i * n + j] = -(pair8[i * n + j] + pair8[j * n + i]) * r2.powi(4);
+            }
+        }
+        Ok(DFTD3Coefficients { natoms: n, c6, c8 })
+    }
+
+    /// Evaluate pairwise C6 and C8 coefficients of molecular structure
+    pub fn get_dispersion_coefficients(&self, structure: &DFTD3Structure) -> DFTD3Coefficients {
+        self.get_dispersion_coefficients_f(structure).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Benzene with C-C 1.39 and C-H 1.08 Angstrom (in Bohr)
+    fn benzene() -> DFTD3Structure {
+        #[rustfmt::skip]
+        let positions = [
+             2.626719,  0.000000, 0.0,   1.313360,  2.274806, 0.0,  -1.313360,  2.274806, 0.0,
+            -2.626719,  0.000000, 0.0,  -1.313360, -2.274806, 0.0,   1.313360, -2.274806, 0.0,
+             4.667624,  0.000000, 0.0,   2.333812,  4.042281, 0.0,  -2.333812,  4.042281, 0.0,
+            -4.667624,  0.000000, 0.0,  -2.333812, -4.042281, 0.0,   2.333812, -4.042281, 0.0,
+        ];
+        let numbers = [6, 6, 6, 6, 6, 6, 1, 1, 1, 1, 1, 1];
+        DFTD3Structure::new(12, &numbers, &positions, None, None)
+    }
+
+    #[test]
+    fn test_benzene_coordination_numbers() {
+        let structure = benzene();
+        let model = DFTD3Model::new(&structure);
+        let cn = model.get_coordination_numbers(&structure);
+
+        // reference with k1 = 16 and covalent radii `rcov` (in Bohr, scaled by 4/3) of the DFT-D3
+        // program (Grimme et al., J. Chem. Phys. 132, 154104 (2010))
+        assert!(cn[..6].iter().all(|&x| (x - 3.138044).abs() < 1e-5));
+        assert!(cn[6..].iter().all(|&x| (x - 1.003837).abs() < 1e-5));
+
+        let coefficients = model.get_dispersion_coefficients(&structure);
+        let n = 12;
+        for i in 0..n {
+            for j in (0..n).filter(|&j| j != i) {
+                let (c6_ij, c6_ji) = (coefficients.c6[i * n + j], coefficients.c6[j * n + i]);
+                assert!(c6_ij > 0.0 && (c6_ij - c6_ji).abs() < 1e-8 * c6_ij);
+                assert!(coefficients.c8[i * n + j] > c6_ij);
+            }
+        }
+        // carbon-carbon C6 is larger than hydrogen-hydrogen C6
+        assert!(coefficients.c6[1] > coefficients.c6[6 * n + 7]);
+        let atomic_c6 = coefficients.atomic_c6();
+        assert!((atomic_c6[0] - atomic_c6[5]).abs() < 1e-8 * atomic_c6[0]);
+        assert!(atomic_c6[0] > atomic_c6[6]);
+    }
+
+    #[test]
+    fn test_d3_covalent_radii() {
+        // `rcov` (in Bohr, scaled by 4/3) of the DFT-D3 program; metals are decreased by 10%
+        // compared to the radii of Pyykko and Atsumi
+        let reference = [
+            (1, 0.80628308),
+            (3, 3.02356173),
+            (5, 1.94011865),
+            (6, 1.88972601),
+            (11, 3.52748848),
+            (12, 3.14954334),
+            (13, 2.84718717),
+            (14, 2.62041997),
+            (26, 2.62041997),
+        ];
+        for (number, rcov) in reference {
+            assert!((covalent_radius_d3(number).unwrap() - rcov).abs() < 1e-6 * rcov);
+        }
+        assert!(covalent_radius_d3(0).is_none());
+
+        // silane with Si-H 1.48 Angstrom; reference with `rcov` of the DFT-D3 program
+        let a = 1.614730;
+        #[rustfmt::skip]
+        let positions = [
+            0.0, 0.0, 0.0,   a, a, a,   a, -a, -a,   -a, a, -a,   -a, -a, a,
+        ];
+        let structure = DFTD3Structure::new(5, &[14, 1, 1, 1, 1], &positions, None, None);
+        let model = DFTD3Model::new(&structure);
+        let cn = model.get_coordination_numbers(&structure);
+        assert!((cn[0] - 3.893985).abs() < 1e-5);
+        assert!(cn[1..].iter().all(|&x| (x - 0.973592).abs() < 1e-5));
+    }
+
+    #[test]
+    fn test_periodic_coordination_numbers() {
+        // one hydrogen in cubic cell sees its own images at distance a
+        let a = 2.0;
+        let lattice = [a, 0.0, 0.0, 0.0, a, 0.0, 0.0, 0.0, a];
+        let structure = DFTD3Structure::new(
+            1,
+            &[1],
+            &[0.0; 3],
+            Some(&lattice),
+            Some(&[true, true, true]),
+        );
+        // cutoff between next-nearest (sqrt(2) a) and third-nearest (sqrt(3) a) images
+        let cn = get_coordination_numbers(&structure, 1.6 * a);
+        let rc = 2.0 * covalent_radius_d3(1).unwrap();
+        let count = |r: f64| 1.0 / (1.0 + (-CN_STEEPNESS * (rc / r - 1.0)).exp());
+        let reference = 6.0 * count(a) + 12.0 * count(a * 2.0_f64.sqrt());
+        assert!((cn[0] - reference).abs() < 1e-12);
+    }
+}
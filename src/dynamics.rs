@@ -0,0 +1,473 @@
+//! Velocity-Verlet molecular dynamics with dispersion forces.
+//!
+//! Forces are those of D3 dispersion, optionally plus a user-provided base potential (see
+//! [`UserEnergy`]). The structure is moved by [`DFTD3Structure::update_f`] at each step, so that
+//! the model is created only once. Masses are taken from [`atomic_mass`].
+//!
+//! Positions, velocities and energies are in atomic units; the time step and logged times are in
+//! femtoseconds, and temperatures in Kelvin. Supported ensembles are NVE and NVT by Berendsen
+//! velocity rescaling or Langevin dynamics (Ornstein-Uhlenbeck velocity update after each step).
+
+use crate::prelude::*;
+use crate::trajectory::write_extxyz_frame;
+use std::io::Write;
+
+/// User-provided base potential of molecular dynamics
+pub type UserPotential<'a> = Box<dyn FnMut(&DFTD3Structure) -> Result<UserEnergy, DFTD3Error> + 'a>;
+
+/// Thermostat of molecular dynamics
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Thermostat {
+    /// microcanonical (NVE) dynamics
+    None,
+    /// Berendsen velocity rescaling to `temperature` (K) with coupling time `tau` (fs)
+    Berendsen { temperature: f64, tau: f64 },
+    /// Langevin dynamics at `temperature` (K) with `friction` (1/fs)
+    Langevin { temperature: f64, friction: f64 },
+}
+
+/// Energies of one step of molecular dynamics
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DynamicsLog {
+    /// number of performed steps
+    pub step: usize,
+    /// simulation time (in fs)
+    pub time: f64,
+    /// potential energy
+    pub potential: f64,
+    /// kinetic energy
+    pub kinetic: f64,
+    /// instantaneous temperature (in K)
+    pub temperature: f64,
+}
+
+impl DynamicsLog {
+    /// Total (potential plus kinetic) energy
+    pub fn get_total_energy(&self) -> f64 {
+        self.potential + self.kinetic
+    }
+}
+
+/// Pseudo-random numbers (splitmix64) with normal deviates by Box-Muller transform
+struct Random(u64);
+
+impl Random {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// uniform in (0, 1]
+    fn uniform(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    fn normal(&mut self) -> f64 {
+        let (u1, u2) = (self.uniform(), self.uniform());
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+/// Velocity-Verlet molecular dynamics driver
+pub struct MolecularDynamics<'a> {
+    structure: &'a DFTD3Structure,
+    model: &'a DFTD3Model,
+    param: &'a DFTD3Param,
+    user: Option<UserPotential<'a>>,
+    thermostat: Thermostat,
+    // time step in atomic units
+    timestep: f64,
+    // masses in electron masses
+    masses: Vec<f64>,
+    velocities: Vec<f64>,
+    // potential energy and forces at current positions, evaluated lazily
+    forces: Option<(f64, Vec<f64>)>,
+    random: Random,
+    step: usize,
+}
+
+impl<'a> MolecularDynamics<'a> {
+    /// Create NVE driver with time step (in fs) and zero velocities (failable)
+    pub fn new_f(
+        structure: &'a DFTD3Structure,
+        model: &'a DFTD3Model,
+        param: &'a DFTD3Param,
+        timestep: f64,
+    ) -> Result<Self, DFTD3Error> {
+        let masses = structure
+            .get_numbers()
+            .iter()
+            .map(|&n| {
+                atomic_mass(n)
+                    .map(|m| m * DALTON_TO_ELECTRON_MASS)
+                    .ok_or_else(|| DFTD3Error::Rust(format!("No atomic mass of element {}", n)))
+            })
+            .collect::<Result<Vec<f64>, DFTD3Error>>()?;
+        if timestep.is_nan() || timestep <= 0.0 {
+            return Err(DFTD3Error::Rust(format!(
+                "Invalid time step, expected positive value, got {}",
+                timestep
+            )));
+        }
+        let natoms = structure.get_natoms();
+        Ok(Self {
+            structure,
+            model,
+            param,
+            user: None,
+            thermostat: Thermostat::None,
+            timestep: timestep / AU_TIME_TO_FS,
+            masses,
+            velocities: vec![0.0; 3 * natoms],
+            forces: None,
+            random: Random(0),
+            step: 0,
+        })
+    }
+
+    /// Create NVE driver with time step (in fs) and zero velocities
+    pub fn new(
+        structure: &'a DFTD3Structure,
+        model: &'a DFTD3Model,
+        param: &'a DFTD3Param,
+        timestep: f64,
+    ) -> Self {
+        Self::new_f(structure, model, param, timestep).unwrap()
+    }
+
+    /// Add user-provided base potential to dispersion
+    pub fn with_user_potential(
+        mut self,
+        user: impl FnMut(&DFTD3Structure) -> Result<UserEnergy, DFTD3Error> + 'a,
+    ) -> Self {
+        self.user = Some(Box::new(user));
+        self.forces = None;
+        self
+    }
+
+    /// Set thermostat
+    pub fn with_thermostat(mut self, thermostat: Thermostat) -> Self {
+        self.thermostat = thermostat;
+        self
+    }
+
+    /// Set seed of random numbers (Langevin dynamics and initial velocities)
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.random = Random(seed);
+        self
+    }
+
+    /// Get structure at current step
+    pub fn get_structure(&self) -> &DFTD3Structure {
+        self.structure
+    }
+
+    /// Get masses (in electron masses) [natoms]
+    pub fn get_masses(&self) -> &[f64] {
+        &self.masses
+    }
+
+    /// Get velocities [natoms][3]
+    pub fn get_velocities(&self) -> &[f64] {
+        &self.velocities
+    }
+
+    /// Set velocities [natoms][3] (failable)
+    pub fn set_velocities_f(&mut self, velocities: &[f64]) -> Result<(), DFTD3Error> {
+        if velocities.len() != self.velocities.len() {
+            return Err(DFTD3Error::Rust(format!(
+                "Invalid dimension for velocities, expected {}, got {}",
+                self.velocities.len(),
+                velocities.len()
+            )));
+        }
+        self.velocities.copy_from_slice(velocities);
+        Ok(())
+    }
+
+    /// Set velocities [natoms][3]
+    pub fn set_velocities(&mut self, velocities: &[f64]) {
+        self.set_velocities_f(velocities).unwrap()
+    }
+
+    /// Draw Maxwell-Boltzmann velocities, remove center-of-mass motion and rescale to temperature
+    pub fn initialize_velocities(&mut self, temperature: f64) {
+        let kt = BOLTZMANN_HARTREE_PER_KELVIN * temperature;
+        for (i, v) in self.velocities.iter_mut().enumerate() {
+            *v = (kt / self.masses[i / 3]).sqrt() * self.random.normal();
+        }
+        let total_mass = self.masses.iter().sum::<f64>();
+        for k in 0..3 {
+            let momentum = (0..self.masses.len())
+                .map(|i| self.masses[i] * self.velocities[3 * i + k])
+                .sum::<f64>();
+            for i in 0..self.masses.len() {
+                self.velocities[3 * i + k] -= momentum / total_mass;
+            }
+        }
+        let current = self.get_temperature();
+        if current > 0.0 {
+            let scale = (temperature / current).sqrt();
+            self.velocities.iter_mut().for_each(|v| *v *= scale);
+        }
+    }
+
+    /// Kinetic energy
+    pub fn get_kinetic_energy(&self) -> f64 {
+        self.velocities
+            .iter()
+            .enumerate()
+            .map(|(i, v)| 0.5 * self.masses[i / 3] * v * v)
+            .sum()
+    }
+
+    /// Number of degrees of freedom (center-of-mass motion removed for more than one atom)
+    pub fn get_degrees_of_freedom(&self) -> usize {
+        match self.masses.len() {
+            0 | 1 => 3 * self.masses.len(),
+            n => 3 * n - 3,
+        }
+    }
+
+    /// Instantaneous temperature (in K)
+    pub fn get_temperature(&self) -> f64 {
+        let ndof = self.get_degrees_of_freedom().max(1) as f64;
+        2.0 * self.get_kinetic_energy() / (ndof * BOLTZMANN_HARTREE_PER_KELVIN)
+    }
+
+    /// Evaluate potential energy and forces at current positions
+    fn evaluate_f(&mut self) -> Result<(f64, Vec<f64>), DFTD3Error> {
+        let (mut energy, gradient, _) =
+            get_dispersion_f(self.structure, self.model, self.param, true, false)?;
+        let mut forces = gradient.unwrap().iter().map(|x| -x).collect::<Vec<f64>>();
+        if let Some(user) = &mut self.user {
+            let (user_energy, user_gradient, _) = user(self.structure)?;
+            if user_gradient.len() != forces.len() {
+                return Err(DFTD3Error::Rust(format!(
+                    "Invalid dimension for user gradient, expected {}, got {}",
+                    forces.len(),
+                    user_gradient.len()
+                )));
+            }
+            energy += user_energy;
+            forces
+                .iter_mut()
+                .zip(&user_gradient)
+                .for_each(|(f, g)| *f -= g);
+        }
+        Ok((energy, forces))
+    }
+
+    /// Energies at current step (failable)
+    pub fn get_log_f(&mut self) -> Result<DynamicsLog, DFTD3Error> {
+        if self.forces.is_none() {
+            self.forces = Some(self.evaluate_f()?);
+        }
+        Ok(DynamicsLog {
+            step: self.step,
+            time: self.step as f64 * self.timestep * AU_TIME_TO_FS,
+            potential: self.forces.as_ref().unwrap().0,
+            kinetic: self.get_kinetic_energy(),
+            temperature: self.get_temperature(),
+        })
+    }
+
+    /// Perform one velocity-Verlet step followed by thermostat (failable)
+    pub fn step_f(&mut self) -> Result<DynamicsLog, DFTD3Error> {
+        let dt = self.timestep;
+        if self.forces.is_none() {
+            self.forces = Some(self.evaluate_f()?);
+        }
+        let (_, forces) = self.forces.take().unwrap();
+        let mut positions = self.structure.get_positions();
+        for (i, (v, x)) in self.velocities.iter_mut().zip(&mut positions).enumerate() {
+            *v += 0.5 * dt * forces[i] / self.masses[i / 3];
+            *x += dt * *v;
+        }
+        self.structure.update_f(&positions, None)?;
+        let (energy, forces) = self.evaluate_f()?;
+        for (i, v) in self.velocities.iter_mut().enumerate() {
+            *v += 0.5 * dt * forces[i] / self.masses[i / 3];
+        }
+        self.forces = Some((energy, forces));
+
+        match self.thermostat {
+            Thermostat::None => (),
+            Thermostat::Berendsen { temperature, tau } => {
+                let current = self.get_temperature();
+                if current > 0.0 {
+                    let ratio = dt * AU_TIME_TO_FS / tau;
+                    let scale = (1.0 + ratio * (temperature / current - 1.0))
+                        .max(0.0)
+                        .sqrt();
+                    self.velocities.iter_mut().for_each(|v| *v *= scale);
+                }
+            }
+            Thermostat::Langevin {
+                temperature,
+                friction,
+            } => {
+                let c1 = (-friction * dt * AU_TIME_TO_FS).exp();
+                let c2 = (1.0 - c1 * c1).sqrt();
+                let kt = BOLTZMANN_HARTREE_PER_KELVIN * temperature;
+                for (i, v) in self.velocities.iter_mut().enumerate() {
+                    let sigma = (kt / self.masses[i / 3]).sqrt();
+                    *v = c1 * *v + c2 * sigma * self.random.normal();
+                }
+            }
+        }
+        self.step += 1;
+        self.get_log_f()
+    }
+
+    /// Perform one velocity-Verlet step followed by thermostat
+    pub fn step(&mut self) -> DynamicsLog {
+        self.step_f().unwrap()
+    }
+
+    /// Run steps, writing extended XYZ frames (energy in eV) and energy log (failable)
+    ///
+    /// Frames and log lines (step, time in fs, potential, kinetic and total energy in Hartree,
+    /// temperature in K) are written for the initial step and every `interval` steps.
+    pub fn run_f(
+        &mut self,
+        nsteps: usize,
+        interval: usize,
+        mut trajectory: Option<&mut dyn Write>,
+        mut log: Option<&mut dyn Write>,
+    ) -> Result<Vec<DynamicsLog>, DFTD3Error> {
+        let io_error =
+            |err: std::io::Error| DFTD3Error::Rust(format!("Dynamics I/O error: {}", err));
+        let interval = interval.max(1);
+        let mut logs = vec![];
+        if let Some(log) = log.as_mut() {
+            writeln!(log, "# step time potential kinetic total temperature").map_err(io_error)?;
+        }
+        for istep in 0..=nsteps {
+            let entry = match istep {
+                0 => self.get_log_f()?,
+                _ => self.step_f()?,
+            };
+            if istep % interval != 0 && istep != nsteps {
+                continue;
+            }
+            if let Some(trajectory) = trajectory.as_mut() {
+                let properties = format!(
+                    "energy={:.12} time={:.6} temperature={:.6}",
+                    entry.potential * HARTREE_TO_EV,
+                    entry.time,
+                    entry.temperature
+                );
+                write_extxyz_frame(
+                    trajectory,
+                    self.structure.get_numbers(),
+                    &self.structure.get_positions(),
+                    self.structure.get_lattice().as_deref(),
+                    self.structure.get_periodic(),
+                    &properties,
+                )
+                .map_err(io_error)?;
+            }
+            if let Some(log) = log.as_mut() {
+                writeln!(
+                    log,
+                    "{} {:.6} {:.12} {:.12} {:.12} {:.6}",
+                    entry.step,
+                    entry.time,
+                    entry.potential,
+                    entry.kinetic,
+                    entry.get_total_energy(),
+                    entry.temperature
+                )
+                .map_err(io_error)?;
+            }
+            logs.push(entry);
+        }
+        Ok(logs)
+    }
+
+    /// Run steps, writing extended XYZ frames and energy log
+    pub fn run(
+        &mut self,
+        nsteps: usize,
+        interval: usize,
+        trajectory: Option<&mut dyn Write>,
+        log: Option<&mut dyn Write>,
+    ) -> Vec<DynamicsLog> {
+        self.run_f(nsteps, interval, trajectory, log).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dynamics_water_dimer() {
+        #[rustfmt::skip]
+        let positions = [
+            0.000, 0.000, 0.000,   1.810, 0.000, 0.000,  -0.450, 1.750, 0.000,
+            5.600, 0.000, 0.000,   6.200, 1.700, 0.000,   6.200,-1.700, 0.000,
+        ];
+        let numbers = [8, 1, 1, 8, 1, 1];
+        let param = DFTD3Param::load_rational_damping("B3LYP", false);
+        // keep molecules intact by harmonic restraint of intramolecular distances to initial ones
+        let restraint = |structure: &DFTD3Structure| {
+            let x = structure.get_positions();
+            let mut energy = 0.0;
+            let mut gradient = vec![0.0; 18];
+            for (i, j) in [(0, 1), (0, 2), (1, 2), (3, 4), (3, 5), (4, 5)] {
+                let dist = |x: &[f64]| {
+                    let d = [0, 1, 2].map(|k| x[3 * i + k] - x[3 * j + k]);
+                    (d, (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt())
+                };
+                let (d, r) = dist(&x);
+                let (_, r0) = dist(&positions);
+                energy += 0.25 * (r - r0).powi(2);
+                for k in 0..3 {
+                    gradient[3 * i + k] += 0.5 * (r - r0) * d[k] / r;
+                    gradient[3 * j + k] -= 0.5 * (r - r0) * d[k] / r;
+                }
+            }
+            Ok((energy, gradient, None))
+        };
+
+        // NVE conserves total energy
+        let structure = DFTD3Structure::new(6, &numbers, &positions, None, None);
+        let model = DFTD3Model::new(&structure);
+        let mut md = MolecularDynamics::new(&structure, &model, &param, 0.2)
+            .with_user_potential(restraint)
+            .with_seed(7);
+        md.initialize_velocities(300.0);
+        assert!((md.get_temperature() - 300.0).abs() < 1e-8);
+        let (mut trajectory, mut log) = (vec![], vec![]);
+        let logs = md.run(200, 50, Some(&mut trajectory), Some(&mut log));
+        assert_eq!(logs.len(), 5);
+        let drift = logs
+            .iter()
+            .map(|x| (x.get_total_energy() - logs[0].get_total_energy()).abs())
+            .fold(0.0, f64::max);
+        assert!(drift < 1e-5);
+        assert_eq!(
+            String::from_utf8(trajectory).unwrap().lines().count(),
+            5 * 8
+        );
+        assert_eq!(String::from_utf8(log).unwrap().lines().count(), 1 + 5);
+
+        // Berendsen thermostat drives temperature towards target
+        let structure = DFTD3Structure::new(6, &numbers, &positions, None, None);
+        let thermostat = Thermostat::Berendsen {
+            temperature: 50.0,
+            tau: 10.0,
+        };
+        let mut md = MolecularDynamics::new(&structure, &model, &param, 0.2)
+            .with_user_potential(restraint)
+            .with_thermostat(thermostat);
+        md.initialize_velocities(500.0);
+        let logs = md.run(500, 500, None, None);
+        assert!(logs.last().unwrap().temperature < 250.0);
+    }
+}
@@ -0,0 +1,112 @@
+//! Element data: symbols, atomic masses and covalent radii.
+
+/// Element symbols, indexed by atomic number (index 0 is dummy `X`)
+#[rustfmt::skip]
+pub const ELEMENT_SYMBOLS: [&str; 119] = [
+    "X", "H", "He", "Li", "Be", "B", "C", "N", "O", "F",
+    "Ne", "Na", "Mg", "Al", "Si", "P", "S", "Cl", "Ar", "K",
+    "Ca", "Sc", "Ti", "V", "Cr", "Mn", "Fe", "Co", "Ni", "Cu",
+    "Zn", "Ga", "Ge", "As", "Se", "Br", "Kr", "Rb", "Sr", "Y",
+    "Zr", "Nb", "Mo", "Tc", "Ru", "Rh", "Pd", "Ag", "Cd", "In",
+    "Sn", "Sb", "Te", "I", "Xe", "Cs", "Ba", "La", "Ce", "Pr",
+    "Nd", "Pm", "Sm", "Eu", "Gd", "Tb", "Dy", "Ho", "Er", "Tm",
+    "Yb", "Lu", "Hf", "Ta", "W", "Re", "Os", "Ir", "Pt", "Au",
+    "Hg", "Tl", "Pb", "Bi", "Po", "At", "Rn", "Fr", "Ra", "Ac",
+    "Th", "Pa", "U", "Np", "Pu", "Am", "Cm", "Bk", "Cf", "Es",
+    "Fm", "Md", "No", "Lr", "Rf", "Db", "Sg", "Bh", "Hs", "Mt",
+    "Ds", "Rg", "Cn", "Nh", "Fl", "Mc", "Lv", "Ts", "Og",
+];
+
+/// Standard atomic masses (in unified atomic mass unit, Dalton), indexed by atomic number
+///
+/// For elements without stable isotopes, mass of the longest-lived isotope is used.
+#[rustfmt::skip]
+pub const ATOMIC_MASSES: [f64; 119] = [
+    0.0, 1.008, 4.002602, 6.94, 9.0121831, 10.81,
+    12.011, 14.007, 15.999, 18.998403163, 20.1797, 22.98976928,
+    24.305, 26.9815385, 28.085, 30.973761998, 32.06, 35.45,
+    39.948, 39.0983, 40.078, 44.955908, 47.867, 50.9415,
+    51.9961, 54.938044, 55.845, 58.933194, 58.6934, 63.546,
+    65.38, 69.723, 72.630, 74.921595, 78.971, 79.904,
+    83.798, 85.4678, 87.62, 88.90584, 91.224, 92.90637,
+    95.95, 97.90721, 101.07, 102.90550, 106.42, 107.8682,
+    112.414, 114.818, 118.710, 121.760, 127.60, 126.90447,
+    131.293, 132.90545196, 137.327, 138.90547, 140.116, 140.90766,
+    144.242, 144.91276, 150.36, 151.964, 157.25, 158.92535,
+    162.500, 164.93033, 167.259, 168.93422, 173.054, 174.9668,
+    178.49, 180.94788, 183.84, 186.207, 190.23, 192.217,
+    195.084, 196.966569, 200.592, 204.38, 207.2, 208.98040,
+    208.98243, 209.98715, 222.01758, 223.01974, 226.02541, 227.02775,
+    232.0377, 231.03588, 238.02891, 237.04817, 244.06421, 243.06138,
+    247.07035, 247.07031, 251.07959, 252.0830, 257.09511, 258.09843,
+    259.1010, 262.110, 267.122, 268.126, 271.134, 270.133,
+    269.1338, 278.156, 281.165, 281.166, 285.177, 286.182,
+    289.190, 289.194, 293.204, 293.208, 294.214,
+];
+
+/// Covalent radii (in Angstrom, Pyykko and Atsumi, 2009), indexed by atomic number
+#[rustfmt::skip]
+pub const COVALENT_RADII_2009: [f64; 119] = [
+    0.0, 0.32, 0.46, 1.33, 1.02, 0.85, 0.75, 0.71, 0.63, 0.64,
+    0.67, 1.55, 1.39, 1.26, 1.16, 1.11, 1.03, 0.99, 0.96, 1.96,
+    1.71, 1.48, 1.36, 1.34, 1.22, 1.19, 1.16, 1.11, 1.10, 1.12,
+    1.18, 1.24, 1.21, 1.21, 1.16, 1.14, 1.17, 2.10, 1.85, 1.63,
+    1.54, 1.47, 1.38, 1.28, 1.25, 1.25, 1.20, 1.28, 1.36, 1.42,
+    1.40, 1.40, 1.36, 1.33, 1.31, 2.32, 1.96, 1.80, 1.63, 1.76,
+    1.74, 1.73, 1.72, 1.68, 1.69, 1.68, 1.67, 1.66, 1.65, 1.64,
+    1.70, 1.62, 1.52, 1.46, 1.37, 1.31, 1.29, 1.22, 1.23, 1.24,
+    1.33, 1.44, 1.44, 1.51, 1.45, 1.47, 1.42, 2.23, 2.01, 1.86,
+    1.75, 1.69, 1.70, 1.71, 1.72, 1.66, 1.66, 1.68, 1.68, 1.65,
+    1.67, 1.73, 1.76, 1.61, 1.57, 1.49, 1.43, 1.41, 1.34, 1.29,
+    1.28, 1.21, 1.22, 1.36, 1.43, 1.62, 1.75, 1.65, 1.57,
+];
+
+/// Covalent radii (in Angstrom) of the D3 coordination number, indexed by atomic number
+///
+/// These are the radii of Pyykko and Atsumi (2009) with values for metals decreased by 10%, as
+/// tabulated in the DFT-D3 program and s-dftd3.
+#[rustfmt::skip]
+pub const COVALENT_RADII_D3: [f64; 119] = [
+    0.0, 0.32, 0.46, 1.20, 0.94, 0.77, 0.75, 0.71, 0.63, 0.64,
+    0.67, 1.40, 1.25, 1.13, 1.04, 1.10, 1.02, 0.99, 0.96, 1.76,
+    1.54, 1.33, 1.22, 1.21, 1.10, 1.07, 1.04, 1.00, 0.99, 1.01,
+    1.09, 1.12, 1.09, 1.15, 1.10, 1.14, 1.17, 1.89, 1.67, 1.47,
+    1.39, 1.32, 1.24, 1.15, 1.13, 1.13, 1.08, 1.15, 1.23, 1.28,
+    1.26, 1.26, 1.23, 1.32, 1.31, 2.09, 1.76, 1.62, 1.47, 1.58,
+    1.57, 1.56, 1.55, 1.51, 1.52, 1.51, 1.50, 1.49, 1.49, 1.48,
+    1.53, 1.46, 1.37, 1.31, 1.23, 1.18, 1.16, 1.11, 1.12, 1.13,
+    1.32, 1.30, 1.30, 1.36, 1.31, 1.38, 1.42, 2.01, 1.81, 1.67,
+    1.58, 1.52, 1.53, 1.54, 1.55, 1.49, 1.49, 1.51, 1.51, 1.48,
+    1.50, 1.56, 1.58, 1.45, 1.41, 1.34, 1.29, 1.27, 1.21, 1.16,
+    1.15, 1.09, 1.22, 1.36, 1.43, 1.46, 1.58, 1.48, 1.57,
+];
+
+/// Element symbol of atomic number
+pub fn element_symbol(number: usize) -> Option<&'static str> {
+    match number {
+        1..=118 => Some(ELEMENT_SYMBOLS[number]),
+        _ => None,
+    }
+}
+
+/// Atomic number of element symbol (case-insensitive, surrounding whitespace ignored)
+pub fn element_number(symbol: &str) -> Option<usize> {
+    let symbol = symbol.trim();
+    (1..=118).find(|&n| ELEMENT_SYMBOLS[n].eq_ignore_ascii_case(symbol))
+}
+
+/// Atomic mass (in Dalton) of atomic number
+pub fn atomic_mass(number: usize) -> Option<f64> {
+    match number {
+        1..=118 => Some(ATOMIC_MASSES[number]),
+        _ => None,
+    }
+}
+
+/// Covalent radius (in Angstrom, Pyykko and Atsumi, 2009) of atomic number
+pub fn covalent_radius(number: usize) -> Option<f64> {
+    match number {
+        1..=118 => Some(COVALENT_RADII_2009[number]),
+        _ => None,
+    }
+}
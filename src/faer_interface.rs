@@ -0,0 +1,122 @@
+//! Interface to `faer` (feature `faer`).
+//!
+//! Positions and gradients are matrices of shape `[natoms, 3]`; lattice and sigma are matrices of
+//! shape `[3, 3]`, where each row of lattice matrix is a lattice vector.
+
+use crate::prelude::*;
+use faer::{Mat, MatRef};
+
+fn to_rows(name: &str, matrix: MatRef<f64>, nrows: usize) -> Result<Vec<[f64; 3]>, DFTD3Error> {
+    if matrix.nrows() != nrows || matrix.ncols() != 3 {
+        return Err(DFTD3Error::Rust(format!(
+            "Invalid dimension for {}, expected [{}, 3], got [{}, {}]",
+            name,
+            nrows,
+            matrix.nrows(),
+            matrix.ncols()
+        )));
+    }
+    Ok((0..nrows)
+        .map(|i| [matrix[(i, 0)], matrix[(i, 1)], matrix[(i, 2)]])
+        .collect())
+}
+
+fn to_lattice(lattice: Option<MatRef<f64>>) -> Result<Option<[[f64; 3]; 3]>, DFTD3Error> {
+    match lattice {
+        Some(lattice) => {
+            let rows = to_rows("lattice", lattice, 3)?;
+            Ok(Some([rows[0], rows[1], rows[2]]))
+        }
+        None => Ok(None),
+    }
+}
+
+impl DFTD3Structure {
+    /// Create new molecular structure data from `faer` (quantities in Bohr) (failable)
+    pub fn from_faer_f(
+        numbers: &[usize],
+        positions: MatRef<f64>,
+        lattice: Option<MatRef<f64>>,
+        periodic: Option<&[bool]>,
+    ) -> Result<Self, DFTD3Error> {
+        let positions = to_rows("positions", positions, positions.nrows())?;
+        let lattice = to_lattice(lattice)?;
+        Self::from_rows_f(numbers, &positions, lattice.as_ref(), periodic)
+    }
+
+    /// Create new molecular structure data from `faer` (quantities in Bohr)
+    ///
+    /// # Arguments
+    ///
+    /// * `numbers` - numbers [natoms]
+    /// * `positions` - positions [natoms, 3]
+    /// * `lattice` - lattice [3, 3]
+    /// * `periodic` - periodic [3]
+    pub fn from_faer(
+        numbers: &[usize],
+        positions: MatRef<f64>,
+        lattice: Option<MatRef<f64>>,
+        periodic: Option<&[bool]>,
+    ) -> Self {
+        Self::from_faer_f(numbers, positions, lattice, periodic).unwrap()
+    }
+
+    /// Update coordinates and lattice parameters from `faer` (quantities in Bohr) (failable)
+    pub fn update_faer_f(
+        &self,
+        positions: MatRef<f64>,
+        lattice: Option<MatRef<f64>>,
+    ) -> Result<(), DFTD3Error> {
+        let positions = to_rows("positions", positions, self.get_natoms())?;
+        let lattice = to_lattice(lattice)?;
+        self.update_rows_f(&positions, lattice.as_ref())
+    }
+
+    /// Update coordinates and lattice parameters from `faer` (quantities in Bohr)
+    pub fn update_faer(&self, positions: MatRef<f64>, lattice: Option<MatRef<f64>>) {
+        self.update_faer_f(positions, lattice).unwrap()
+    }
+}
+
+impl DFTD3Output {
+    /// Gradient as `faer` matrix [natoms, 3]
+    pub fn gradient_faer(&self) -> Option<Mat<f64>> {
+        let gradient = self.gradient_rows()?;
+        Some(Mat::from_fn(gradient.len(), 3, |i, j| gradient[i][j]))
+    }
+
+    /// Sigma as `faer` matrix [3, 3]
+    pub fn sigma_faer(&self) -> Option<Mat<f64>> {
+        let sigma = self.sigma_rows()?;
+        Some(Mat::from_fn(3, 3, |i, j| sigma[i][j]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_faer_roundtrip() {
+        let numbers = vec![1, 1, 8];
+        let flat = vec![0.0, 0.0, 0.0, 0.0, 0.0, 1.4, 1.2, 0.3, 0.7];
+        let positions = Mat::from_fn(3, 3, |i, j| flat[3 * i + j]);
+        let param = DFTD3Param::load_rational_damping("B3LYP", false);
+
+        let structure = DFTD3Structure::new(3, &numbers, &flat, None, None);
+        let model = DFTD3Model::new(&structure);
+        let reference: DFTD3Output = get_dispersion(&structure, &model, &param, true, true).into();
+
+        let structure = DFTD3Structure::from_faer(&numbers, positions.as_ref(), None, None);
+        let model = DFTD3Model::new(&structure);
+        let output: DFTD3Output = get_dispersion(&structure, &model, &param, true, true).into();
+
+        assert!((reference.energy - output.energy).abs() < 1e-12);
+        let gradient = output.gradient_faer().unwrap();
+        assert_eq!((gradient.nrows(), gradient.ncols()), (3, 3));
+        assert_eq!(gradient[(2, 1)], reference.gradient.as_ref().unwrap()[7]);
+
+        let wrong = Mat::<f64>::zeros(3, 2);
+        assert!(DFTD3Structure::from_faer_f(&numbers, wrong.as_ref(), None, None).is_err());
+    }
+}
@@ -0,0 +1,333 @@
+//! Finite-difference Hessian of dispersion energy.
+
+use crate::prelude::*;
+
+/// Options of finite-difference Hessian
+#[derive(Debug, Clone, PartialEq)]
+pub struct HessianOptions {
+    /// displacement step of atomic positions (in Bohr)
+    pub step: f64,
+    /// strain step for cell-strain second derivatives
+    pub strain_step: f64,
+    /// number of threads for displacements; serial evaluation if not larger than 1
+    pub nthreads: usize,
+    /// symmetrize the Hessian by averaging with its transpose
+    pub symmetrize: bool,
+    /// also evaluate cell-strain second derivatives (periodic systems only)
+    pub strain: bool,
+}
+
+impl Default for HessianOptions {
+    fn default() -> Self {
+        Self {
+            step: 5.0e-3,
+            strain_step: 1.0e-4,
+            nthreads: 1,
+            symmetrize: true,
+            strain: false,
+        }
+    }
+}
+
+/// Result of finite-difference Hessian
+#[derive(Debug, Clone, PartialEq)]
+pub struct DFTD3Hessian {
+    /// Hessian of atomic positions [3 * natoms][3 * natoms]
+    pub hessian: Vec<f64>,
+    /// cell-strain second derivatives [9][9], if evaluated
+    pub strain_hessian: Option<Vec<f64>>,
+}
+
+/// Apply homogeneous strain `r' = (1 + eps) r` to positions [natoms][3] and lattice [3][3].
+pub(crate) fn apply_strain(
+    positions: &[f64],
+    lattice: Option<&[f64]>,
+    eps: &[f64; 9],
+) -> (Vec<f64>, Option<Vec<f64>>) {
+    let deform = |vectors: &[f64]| {
+        vectors
+            .chunks_exact(3)
+            .flat_map(|r| {
+                (0..3).map(move |a| r[a] + (0..3).map(|b| eps[3 * a + b] * r[b]).sum::<f64>())
+            })
+            .collect::<Vec<f64>>()
+    };
+    (deform(positions), lattice.map(deform))
+}
+
+fn symmetrize(matrix: &mut [f64], n: usize) {
+    for i in 0..n {
+        for j in 0..i {
+            let value = 0.5 * (matrix[i * n + j] + matrix[j * n + i]);
+            matrix[i * n + j] = value;
+            matrix[j * n + i] = value;
+        }
+    }
+}
+
+/// Column `j` of Hessian by central difference of gradients; leaves structure displaced.
+fn hessian_column_f(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    positions: &[f64],
+    j: usize,
+    step: f64,
+) -> Result<Vec<f64>, DFTD3Error> {
+    let mut displaced = positions.to_vec();
+    displaced[j] = positions[j] + step;
+    structure.update_f(&displaced, None)?;
+    let gradient_p = get_dispersion_f(structure, model, param, true, false)?
+        .1
+        .unwrap();
+    displaced[j] = positions[j] - step;
+    structure.update_f(&displaced, None)?;
+    let gradient_m = get_dispersion_f(structure, model, param, true, false)?
+        .1
+        .unwrap();
+    Ok(gradient_p
+        .iter()
+        .zip(gradient_m)
+        .map(|(p, m)| (p - m) / (2.0 * step))
+        .collect())
+}
+
+/// Hessian columns of given displacement indices, evaluated on a copy of structure.
+fn hessian_columns_copied(
+    copied: DFTD3Structure,
+    param: &DFTD3Param,
+    cutoff: &RealspaceCutoff,
+    positions: &[f64],
+    indices: impl Iterator<Item = usize>,
+    step: f64,
+) -> Result<Vec<(usize, Vec<f64>)>, DFTD3Error> {
+    let model = DFTD3Model::new_f(&copied)?;
+    model.apply_realspace_cutoff_f(cutoff)?;
+    indices
+        .map(|j| {
+            Ok((
+                j,
+                hessian_column_f(&copied, &model, param, positions, j, step)?,
+            ))
+        })
+        .collect()
+}
+
+/// Evaluate the dispersion Hessian by central differences of analytic gradients (failable)
+///
+/// Displacements are performed by [`DFTD3Structure::update_f`] on the given structure and model;
+/// the original positions and lattice are restored afterwards. For parallel evaluation
+/// (`nthreads > 1`), each thread works on its own clone of structure and a new model with the same
+/// realspace cutoffs.
+///
+/// For cell-strain second derivatives, strain is applied as `r' = (1 + eps) r` to both positions
+/// and lattice vectors, and derivatives are obtained by central differences of sigma. The result
+/// element `(ab, cd)` is the second derivative with respect to `eps[a][b]` and `eps[c][d]`.
+pub fn hessian_f(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    options: &HessianOptions,
+) -> Result<DFTD3Hessian, DFTD3Error> {
+    let natoms = structure.get_natoms();
+    let ndim = 3 * natoms;
+    let step = options.step;
+    let positions = structure.get_positions();
+    let lattice = structure.get_lattice();
+    if options.strain && lattice.is_none() {
+        return Err(DFTD3Error::Rust(
+            "Cell-strain derivatives require lattice of periodic structure".to_string(),
+        ));
+    }
+
+    // columns of hessian by displacements
+    let columns = if options.nthreads > 1 {
+        let nthreads = options.nthreads;
+        let cutoff = model.get_realspace_cutoff();
+        let positions = &positions;
+        std::thread::scope(|scope| {
+            let handles = (0..nthreads)
+                .map(|tid| {
+                    let indices = (tid..ndim).step_by(nthreads);
+                    let copied = structure.clone();
+                    scope.spawn(move || {
+                        hessian_columns_copied(copied, param, &cutoff, positions, indices, step)
+                            .map_err(|err| err.get_message())
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Result<Vec<_>, String>>()
+        })
+        .map_err(DFTD3Error::Rust)?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+    } else {
+        let columns = (0..ndim)
+            .map(|j| {
+                Ok((
+                    j,
+                    hessian_column_f(structure, model, param, &positions, j, step)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, DFTD3Error>>();
+        structure.update_f(&positions, None)?;
+        columns?
+    };
+    let mut hessian = vec![0.0; ndim * ndim];
+    for (j, column) in columns {
+        for (i, value) in column.into_iter().enumerate() {
+            hessian[i * ndim + j] = value;
+        }
+    }
+    if options.symmetrize {
+        symmetrize(&mut hessian, ndim);
+    }
+
+    // cell-strain second derivatives
+    let strain_hessian = match (options.strain, &lattice) {
+        (true, Some(lattice)) => {
+            let h = options.strain_step;
+            let sigma = get_dispersion_f(structure, model, param, false, true)?
+                .2
+                .unwrap();
+            let mut strain_hessian = vec![0.0; 81];
+            let mut evaluate = |cd: usize| -> Result<Vec<f64>, DFTD3Error> {
+                let mut sigma_pm = [vec![], vec![]];
+                for (n, sign) in [1.0, -1.0].into_iter().enumerate() {
+                    let mut eps = [0.0; 9];
+                    eps[cd] = sign * h;
+                    let (strained, strained_lattice) =
+                        apply_strain(&positions, Some(lattice), &eps);
+                    structure.update_f(&strained, strained_lattice.as_deref())?;
+                    sigma_pm[n] = get_dispersion_f(structure, model, param, false, true)?
+                        .2
+                        .unwrap();
+                }
+                Ok(sigma_pm[0]
+                    .iter()
+                    .zip(&sigma_pm[1])
+                    .map(|(p, m)| (p - m) / (2.0 * h))
+                    .collect())
+            };
+            let columns = (0..9)
+                .map(&mut evaluate)
+                .collect::<Result<Vec<_>, DFTD3Error>>();
+            structure.update_f(&positions, Some(lattice))?;
+            for (ce, column) in columns?.into_iter().enumerate() {
+                let (c, e) = (ce / 3, ce % 3);
+                for (ab, value) in column.into_iter().enumerate() {
+                    let (a, b) = (ab / 3, ab % 3);
+                    // sigma at strained cell is derivative of energy to strain on top of it;
+                    // the correction term converts it to derivative of total strain
+                    let correction = if b == c { sigma[3 * a + e] } else { 0.0 };
+                    strain_hessian[ab * 9 + ce] = value - correction;
+                }
+            }
+            if options.symmetrize {
+                symmetrize(&mut strain_hessian, 9);
+            }
+            Some(strain_hessian)
+        }
+        _ => None,
+    };
+
+    Ok(DFTD3Hessian {
+        hessian,
+        strain_hessian,
+    })
+}
+
+/// Evaluate the dispersion Hessian by central differences of analytic gradients
+///
+/// See also [`hessian_f`].
+pub fn hessian(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    options: &HessianOptions,
+) -> DFTD3Hessian {
+    hessian_f(structure, model, param, options).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hessian() {
+        #[rustfmt::skip]
+        let positions = [
+            0.000, 0.000, 0.000,   1.810, 0.000, 0.000,  -0.450, 1.750, 0.000,
+            5.600, 0.000, 0.000,   6.200, 1.700, 0.000,   6.200,-1.700, 0.300,
+        ];
+        let numbers = [8, 1, 1, 8, 1, 1];
+        let structure = DFTD3Structure::new(6, &numbers, &positions, None, None);
+        let model = DFTD3Model::new(&structure);
+        let param = DFTD3Param::load_rational_damping("B3LYP", true);
+
+        let options = HessianOptions::default();
+        let result = hessian(&structure, &model, &param, &options);
+        let ndim = 18;
+        // translational sum rule
+        for i in 0..ndim {
+            for k in 0..3 {
+                let sum = (0..6)
+                    .map(|j| result.hessian[i * ndim + 3 * j + k])
+                    .sum::<f64>();
+                assert!(sum.abs() < 1e-6);
+            }
+        }
+        // positions are restored
+        assert_eq!(structure.get_positions(), positions);
+
+        // parallel evaluation gives the same result
+        let options = HessianOptions {
+            nthreads: 4,
+            ..Default::default()
+        };
+        let result_par = hessian(&structure, &model, &param, &options);
+        let diff = result
+            .hessian
+            .iter()
+            .zip(&result_par.hessian)
+            .map(|(a, b)| (a - b).abs());
+        assert!(diff.fold(0.0, f64::max) < 1e-10);
+    }
+
+    #[test]
+    fn test_strain_hessian() {
+        let positions = [0.0, 0.0, 0.0, 1.9, 1.9, 1.9];
+        let lattice = [7.6, 0.0, 0.0, 0.0, 7.6, 0.0, 0.0, 0.0, 7.6];
+        let periodic = [true, true, true];
+        let structure =
+            DFTD3Structure::new(2, &[11, 17], &positions, Some(&lattice), Some(&periodic));
+        let model = DFTD3Model::new(&structure);
+        let param = DFTD3Param::load_rational_damping("PBE", false);
+
+        let options = HessianOptions {
+            strain: true,
+            ..Default::default()
+        };
+        let result = hessian(&structure, &model, &param, &options);
+        let strain_hessian = result.strain_hessian.unwrap();
+
+        // compare diagonal element (xx, xx) with second difference of energy
+        let h = 1.0e-3;
+        let energy = |structure: &DFTD3Structure, value: f64| {
+            let mut eps = [0.0; 9];
+            eps[0] = value;
+            let (strained, strained_lattice) = apply_strain(&positions, Some(&lattice), &eps);
+            structure.update(&strained, strained_lattice.as_deref());
+            get_dispersion(structure, &model, &param, false, false).0
+        };
+        let e0 = energy(&structure, 0.0);
+        let ep = energy(&structure, h);
+        let em = energy(&structure, -h);
+        let reference = (ep + em - 2.0 * e0) / (h * h);
+        assert!((strain_hessian[0] - reference).abs() < 1e-4 * reference.abs().max(1.0));
+    }
+}
@@ -0,0 +1,184 @@
+//! Interaction energy by supermolecule and monomer evaluation.
+
+use crate::prelude::*;
+
+/// Result of interaction energy evaluation
+#[derive(Debug, Clone, PartialEq)]
+pub struct DFTD3Interaction {
+    /// interaction energy (complex energy minus sum of fragment energies)
+    pub energy: f64,
+    /// energy of complex
+    pub complex_energy: f64,
+    /// energies of fragments [nfrag]
+    pub fragment_energies: Vec<f64>,
+    /// gradient of interaction energy with respect to complex coordinates [natoms][3]
+    pub gradient: Vec<f64>,
+    /// gradients of fragments [nfrag][natoms_frag][3], atoms of each fragment in ascending order of
+    /// their indices in complex
+    pub fragment_gradients: Vec<Vec<f64>>,
+    /// counterpoise (gCP) contribution to interaction energy, if evaluated
+    pub gcp_energy: Option<f64>,
+}
+
+/// Evaluate energy and gradient of dispersion (and gCP if requested) for one structure.
+fn evaluate_f(
+    structure: &DFTD3Structure,
+    param: &DFTD3Param,
+    gcp: Option<(&str, &str)>,
+) -> Result<(f64, Vec<f64>, f64), DFTD3Error> {
+    let model = DFTD3Model::new_f(structure)?;
+    let (energy, gradient, _) = get_dispersion_f(structure, &model, param, true, false)?;
+    let mut gradient = gradient.unwrap();
+    let gcp_energy = match gcp {
+        Some((method, basis)) => {
+            let gcp = DFTD3GCP::load_gcp_param_f(structure, method, basis)?;
+            let (gcp_energy, gcp_gradient, _) = get_counterpoise_f(structure, &gcp)?;
+            gradient
+                .iter_mut()
+                .zip(gcp_gradient)
+                .for_each(|(g, x)| *g += x);
+            gcp_energy
+        }
+        None => 0.0,
+    };
+    Ok((energy + gcp_energy, gradient, gcp_energy))
+}
+
+/// Evaluate interaction energy of fragments in complex (failable)
+///
+/// # Arguments
+///
+/// * `structure` - structure of complex
+/// * `fragments` - fragment index of each atom [natoms]; number of fragments is the maximum index
+///   plus one
+/// * `param` - damping parameters
+/// * `gcp` - method and basis of geometric counter-poise correction (e.g. `Some(("b3lyp", "def2svp"))`)
+///
+/// Fragments are evaluated at the geometry of complex (no relaxation). Gradient of each fragment is
+/// returned in [`DFTD3Interaction::fragment_gradients`], and is also scattered back to the atoms of
+/// complex, so that [`DFTD3Interaction::gradient`] is that of interaction energy.
+pub fn interaction_energy_f(
+    structure: &DFTD3Structure,
+    fragments: &[usize],
+    param: &DFTD3Param,
+    gcp: Option<(&str, &str)>,
+) -> Result<DFTD3Interaction, DFTD3Error> {
+    let natoms = structure.get_natoms();
+    if fragments.len() != natoms {
+        return Err(DFTD3Error::Rust(format!(
+            "Invalid dimension for fragments, expected {}, got {}",
+            natoms,
+            fragments.len()
+        )));
+    }
+    let nfrag = fragments.iter().max().map_or(0, |&x| x + 1);
+
+    let (complex_energy, mut gradient, complex_gcp) = evaluate_f(structure, param, gcp)?;
+    let mut fragment_energies = vec![0.0; nfrag];
+    let mut fragment_gradients = vec![vec![]; nfrag];
+    let mut gcp_energy = complex_gcp;
+    for ifrag in 0..nfrag {
+        let indices = (0..natoms)
+            .filter(|&i| fragments[i] == ifrag)
+            .collect::<Vec<usize>>();
+        if indices.is_empty() {
+            continue;
+        }
+        let fragment = structure.extract_f(&indices)?;
+        let (energy, fragment_gradient, fragment_gcp) = evaluate_f(&fragment, param, gcp)?;
+        for (n, &i) in indices.iter().enumerate() {
+            for k in 0..3 {
+                gradient[3 * i + k] -= fragment_gradient[3 * n + k];
+            }
+        }
+        fragment_energies[ifrag] = energy;
+        fragment_gradients[ifrag] = fragment_gradient;
+        gcp_energy -= fragment_gcp;
+    }
+
+    Ok(DFTD3Interaction {
+        energy: complex_energy - fragment_energies.iter().sum::<f64>(),
+        complex_energy,
+        fragment_energies,
+        gradient,
+        fragment_gradients,
+        gcp_energy: gcp.map(|_| gcp_energy),
+    })
+}
+
+/// Evaluate interaction energy of fragments in complex
+///
+/// See also [`interaction_energy_f`].
+pub fn interaction_energy(
+    structure: &DFTD3Structure,
+    fragments: &[usize],
+    param: &DFTD3Param,
+    gcp: Option<(&str, &str)>,
+) -> DFTD3Interaction {
+    interaction_energy_f(structure, fragments, param, gcp).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interaction_energy() {
+        // water dimer (O H H) x 2
+        #[rustfmt::skip]
+        let positions = [
+            0.000, 0.000, 0.000,   1.810, 0.000, 0.000,  -0.450, 1.750, 0.000,
+            5.600, 0.000, 0.000,   6.200, 1.700, 0.000,   6.200,-1.700, 0.000,
+        ];
+        let numbers = [8, 1, 1, 8, 1, 1];
+        let fragments = [0, 0, 0, 1, 1, 1];
+        let structure = DFTD3Structure::new(6, &numbers, &positions, None, None);
+        let param = DFTD3Param::load_rational_damping("B3LYP", false);
+
+        let result = interaction_energy(&structure, &fragments, &param, None);
+        assert!(result.energy < 0.0);
+        assert!(result.gcp_energy.is_none());
+
+        // reference by evaluating structures by hand
+        let monomer = |idx: std::ops::Range<usize>| -> (f64, Vec<f64>) {
+            let structure = DFTD3Structure::new(
+                3,
+                &numbers[idx.clone()],
+                &positions[3 * idx.start..3 * idx.end],
+                None,
+                None,
+            );
+            let model = DFTD3Model::new(&structure);
+            let (energy, gradient, _) = get_dispersion(&structure, &model, &param, true, false);
+            (energy, gradient.unwrap())
+        };
+        let model = DFTD3Model::new(&structure);
+        let complex = get_dispersion(&structure, &model, &param, false, false).0;
+        let (energy_a, gradient_a) = monomer(0..3);
+        let (energy_b, gradient_b) = monomer(3..6);
+        let reference = complex - energy_a - energy_b;
+        assert!((result.energy - reference).abs() < 1e-12);
+
+        // fragment gradients
+        assert_eq!(result.fragment_gradients.len(), 2);
+        for (gradient, reference) in [
+            (&result.fragment_gradients[0], &gradient_a),
+            (&result.fragment_gradients[1], &gradient_b),
+        ] {
+            assert_eq!(gradient.len(), 9);
+            assert!(gradient
+                .iter()
+                .zip(reference)
+                .all(|(a, b)| (a - b).abs() < 1e-12));
+        }
+
+        // translational invariance of interaction gradient
+        for k in 0..3 {
+            let sum = (0..6).map(|i| result.gradient[3 * i + k]).sum::<f64>();
+            assert!(sum.abs() < 1e-10);
+        }
+
+        let result = interaction_energy(&structure, &fragments, &param, Some(("b3lyp", "def2svp")));
+        assert!(result.gcp_energy.is_some());
+    }
+}
@@ -1,8 +1,49 @@
 #![allow(non_camel_case_types)]
 
+pub mod check;
+pub mod cutoff;
+pub mod descriptors;
+pub mod dynamics;
+pub mod elements;
+#[cfg(feature = "faer")]
+pub mod faer_interface;
 pub mod ffi;
+pub mod hessian;
+pub mod interaction;
 pub mod library;
+pub mod mask;
+#[cfg(feature = "nalgebra")]
+pub mod nalgebra_interface;
+#[cfg(feature = "ndarray")]
+pub mod ndarray_interface;
+pub mod optimize;
+pub mod pairwise;
+#[cfg(feature = "python")]
+pub mod python_interface;
 pub mod rest_interface;
+pub mod scaling;
+pub mod server;
+#[cfg(feature = "json")]
+pub mod service;
+pub mod stress;
+pub mod trajectory;
+pub mod units;
 pub mod prelude {
+    pub use crate::check::*;
+    pub use crate::cutoff::*;
+    pub use crate::descriptors::*;
+    pub use crate::dynamics::*;
+    pub use crate::elements::*;
+    pub use crate::hessian::*;
+    pub use crate::interaction::*;
     pub use crate::library::*;
+    pub use crate::mask::*;
+    #[cfg(feature = "ndarray")]
+    pub use crate::ndarray_interface::*;
+    pub use crate::optimize::*;
+    pub use crate::pairwise::*;
+    pub use crate::scaling::*;
+    pub use crate::stress::*;
+    pub use crate::trajectory::*;
+    pub use crate::units::*;
 }
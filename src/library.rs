@@ -1,5 +1,8 @@
+use crate::cutoff::RealspaceCutoff;
+use crate::elements::{atomic_mass, element_symbol};
 use crate::ffi;
-use std::ffi::{c_char, c_int, CStr};
+use std::cell::{Cell, RefCell};
+use std::ffi::{c_char, c_int, CStr, CString, NulError};
 use std::ptr::{null, null_mut};
 use std::result::Result;
 
@@ -23,13 +26,15 @@ pub fn get_api_version_compact() -> [usize; 3] {
 pub enum DFTD3Error {
     C(ffi::dftd3_error),
     Rust(String),
+    /// string argument (e.g. method or basis name) with interior NUL byte
+    InvalidString(NulError),
 }
 
 impl Drop for DFTD3Error {
     fn drop(&mut self) {
         match self {
             DFTD3Error::C(ptr) => unsafe { ffi::dftd3_delete_error(&mut ptr.clone()) },
-            DFTD3Error::Rust(_) => (),
+            DFTD3Error::Rust(_) | DFTD3Error::InvalidString(_) => (),
         }
     }
 }
@@ -43,14 +48,14 @@ impl DFTD3Error {
     pub fn check(&self) -> bool {
         match self {
             DFTD3Error::C(ptr) => unsafe { ffi::dftd3_check_error(*ptr) != 0 },
-            DFTD3Error::Rust(_) => true,
+            DFTD3Error::Rust(_) | DFTD3Error::InvalidString(_) => true,
         }
     }
 
     pub fn get_c_ptr(&mut self) -> ffi::dftd3_error {
         match self {
             DFTD3Error::C(ptr) => *ptr,
-            DFTD3Error::Rust(_) => std::ptr::null_mut(),
+            DFTD3Error::Rust(_) | DFTD3Error::InvalidString(_) => std::ptr::null_mut(),
         }
     }
 
@@ -67,10 +72,20 @@ impl DFTD3Error {
                 return msg.to_string_lossy().to_string();
             }
             DFTD3Error::Rust(msg) => msg.clone(),
+            DFTD3Error::InvalidString(err) => format!(
+                "Invalid string {:?}, interior NUL byte at position {}",
+                String::from_utf8_lossy(&err.clone().into_vec()),
+                err.nul_position()
+            ),
         }
     }
 }
 
+/// Convert string argument to C string (failable)
+fn to_c_string(value: &str) -> Result<CString, DFTD3Error> {
+    CString::new(value).map_err(DFTD3Error::InvalidString)
+}
+
 impl std::fmt::Debug for DFTD3Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         if self.check() {
@@ -96,6 +111,11 @@ impl std::error::Error for DFTD3Error {}
 pub struct DFTD3Structure {
     ptr: ffi::dftd3_structure,
     natoms: usize,
+    // rust-side mirror of structure data held by C handle
+    numbers: Vec<usize>,
+    positions: RefCell<Vec<f64>>,
+    lattice: RefCell<Option<Vec<f64>>>,
+    periodic: Option<Vec<bool>>,
 }
 
 impl Drop for DFTD3Structure {
@@ -104,12 +124,122 @@ impl Drop for DFTD3Structure {
     }
 }
 
+impl Clone for DFTD3Structure {
+    /// Duplicate structure by creating a new C handle from rust-side data
+    fn clone(&self) -> Self {
+        Self::new(
+            self.natoms,
+            &self.numbers,
+            &self.positions.borrow(),
+            self.lattice.borrow().as_deref(),
+            self.periodic.as_deref(),
+        )
+    }
+}
+
+// The C handle is exclusively owned; not `Sync`, since coordinates are updated by `&self`.
+unsafe impl Send for DFTD3Structure {}
+
 impl DFTD3Structure {
     /// Get number of atoms
     pub fn get_natoms(&self) -> usize {
         self.natoms
     }
 
+    /// Get atomic numbers [natoms]
+    pub fn get_numbers(&self) -> &[usize] {
+        &self.numbers
+    }
+
+    /// Get positions [natoms][3] (quantities in Bohr)
+    pub fn get_positions(&self) -> Vec<f64> {
+        self.positions.borrow().clone()
+    }
+
+    /// Get lattice [3][3] (quantities in Bohr), if given
+    pub fn get_lattice(&self) -> Option<Vec<f64>> {
+        self.lattice.borrow().clone()
+    }
+
+    /// Get periodicity [3], if given
+    pub fn get_periodic(&self) -> Option<&[bool]> {
+        self.periodic.as_deref()
+    }
+
+    /// Get element symbols [natoms]
+    pub fn get_symbols(&self) -> Vec<&'static str> {
+        self.numbers
+            .iter()
+            .map(|&x| element_symbol(x).unwrap_or("X"))
+            .collect()
+    }
+
+    /// Get center of mass [3] (quantities in Bohr) (failable)
+    pub fn get_center_of_mass_f(&self) -> Result<[f64; 3], DFTD3Error> {
+        let masses = self
+            .numbers
+            .iter()
+            .map(|&x| {
+                atomic_mass(x).ok_or_else(|| {
+                    DFTD3Error::Rust(format!("No atomic mass for atomic number {}", x))
+                })
+            })
+            .collect::<Result<Vec<f64>, DFTD3Error>>()?;
+        let total = masses.iter().sum::<f64>();
+        if total <= 0.0 {
+            return Err(DFTD3Error::Rust(
+                "Center of mass requires structure of non-zero total mass".to_string(),
+            ));
+        }
+        let mut center = [0.0; 3];
+        for (mass, r) in masses.iter().zip(self.positions.borrow().chunks_exact(3)) {
+            for k in 0..3 {
+                center[k] += mass * r[k] / total;
+            }
+        }
+        Ok(center)
+    }
+
+    /// Get center of mass [3] (quantities in Bohr)
+    pub fn get_center_of_mass(&self) -> [f64; 3] {
+        self.get_center_of_mass_f().unwrap()
+    }
+
+    /// Create new structure of selected atoms, with the same lattice and periodicity (failable)
+    pub fn extract_f(&self, indices: &[usize]) -> Result<Self, DFTD3Error> {
+        if let Some(&idx) = indices.iter().find(|&&idx| idx >= self.natoms) {
+            return Err(DFTD3Error::Rust(format!(
+                "Invalid atom index {}, number of atoms is {}",
+                idx, self.natoms
+            )));
+        }
+        let numbers = indices
+            .iter()
+            .map(|&i| self.numbers[i])
+            .collect::<Vec<usize>>();
+        let positions = self.positions.borrow();
+        let positions = indices
+            .iter()
+            .flat_map(|&i| positions[3 * i..3 * i + 3].iter().copied())
+            .collect::<Vec<f64>>();
+        Self::new_f(
+            indices.len(),
+            &numbers,
+            &positions,
+            self.lattice.borrow().as_deref(),
+            self.periodic.as_deref(),
+        )
+    }
+
+    /// Create new structure of selected atoms, with the same lattice and periodicity
+    ///
+    /// # Arguments
+    ///
+    /// * `indices` - indices of selected atoms
+    pub fn extract(&self, indices: &[usize]) -> Self {
+        self.extract_f(indices).unwrap()
+    }
+
     /// Create new molecular structure data (quantities in Bohr) (failable)
     pub fn new_f(
         natoms: usize,
@@ -159,7 +289,14 @@ impl DFTD3Structure {
         };
         match error.check() {
             true => Err(error),
-            false => Ok(Self { ptr, natoms }),
+            false => Ok(Self {
+                ptr,
+                natoms,
+                numbers: numbers.to_vec(),
+                positions: RefCell::new(positions.to_vec()),
+                lattice: RefCell::new(lattice.map(|x| x.to_vec())),
+                periodic: periodic.map(|x| x.to_vec()),
+            }),
         }
     }
 
@@ -211,7 +348,13 @@ impl DFTD3Structure {
         };
         match error.check() {
             true => Err(error),
-            false => Ok(()),
+            false => {
+                self.positions.borrow_mut().copy_from_slice(positions);
+                if let Some(lattice) = lattice {
+                    *self.lattice.borrow_mut() = Some(lattice.to_vec());
+                }
+                Ok(())
+            }
         }
     }
 
@@ -224,10 +367,61 @@ impl DFTD3Structure {
     pub fn update(&self, positions: &[f64], lattice: Option<&[f64]>) {
         self.update_f(positions, lattice).unwrap()
     }
+
+    /// Create new molecular structure data from per-atom rows (quantities in Bohr) (failable)
+    pub fn from_rows_f(
+        numbers: &[usize],
+        positions: &[[f64; 3]],
+        lattice: Option<&[[f64; 3]; 3]>,
+        periodic: Option<&[bool]>,
+    ) -> Result<Self, DFTD3Error> {
+        let lattice = lattice.map(|x| x.as_flattened());
+        Self::new_f(
+            positions.len(),
+            numbers,
+            positions.as_flattened(),
+            lattice,
+            periodic,
+        )
+    }
+
+    /// Create new molecular structure data from per-atom rows (quantities in Bohr)
+    ///
+    /// # Arguments
+    ///
+    /// * `numbers` - numbers [natoms]
+    /// * `positions` - positions [natoms][3]
+    /// * `lattice` - lattice [3][3], each row is a lattice vector
+    /// * `periodic` - periodic [3]
+    pub fn from_rows(
+        numbers: &[usize],
+        positions: &[[f64; 3]],
+        lattice: Option<&[[f64; 3]; 3]>,
+        periodic: Option<&[bool]>,
+    ) -> Self {
+        Self::from_rows_f(numbers, positions, lattice, periodic).unwrap()
+    }
+
+    /// Update coordinates and lattice parameters from per-atom rows (quantities in Bohr) (failable)
+    pub fn update_rows_f(
+        &self,
+        positions: &[[f64; 3]],
+        lattice: Option<&[[f64; 3]; 3]>,
+    ) -> Result<(), DFTD3Error> {
+        let lattice = lattice.map(|x| x.as_flattened());
+        self.update_f(positions.as_flattened(), lattice)
+    }
+
+    /// Update coordinates and lattice parameters from per-atom rows (quantities in Bohr)
+    pub fn update_rows(&self, positions: &[[f64; 3]], lattice: Option<&[[f64; 3]; 3]>) {
+        self.update_rows_f(positions, lattice).unwrap()
+    }
 }
 
 pub struct DFTD3Model {
     ptr: ffi::dftd3_model,
+    // rust-side mirror of realspace cutoffs applied to C handle
+    cutoff: Cell<RealspaceCutoff>,
 }
 
 impl Drop for DFTD3Model {
@@ -236,6 +430,9 @@ impl Drop for DFTD3Model {
     }
 }
 
+// The C handle is exclusively owned; not `Sync`, since cutoffs are set by `&self`.
+unsafe impl Send for DFTD3Model {}
+
 impl DFTD3Model {
     /// Create new D3 dispersion model (failable)
     pub fn new_f(structure: &DFTD3Structure) -> Result<Self, DFTD3Error> {
@@ -243,7 +440,10 @@ impl DFTD3Model {
         let ptr = unsafe { ffi::dftd3_new_d3_model(error.get_c_ptr(), structure.ptr) };
         match error.check() {
             true => Err(error),
-            false => Ok(Self { ptr }),
+            false => Ok(Self {
+                ptr,
+                cutoff: Cell::new(RealspaceCutoff::default()),
+            }),
         }
     }
 
@@ -253,25 +453,52 @@ impl DFTD3Model {
     }
 
     /// Set realspace cutoffs (quantities in Bohr) (failable)
+    ///
+    /// Cutoffs are validated by [`RealspaceCutoff::validate_f`].
     pub fn set_realspace_cutoff_f(
         &self,
         disp2: f64,
         disp3: f64,
         cn: f64,
     ) -> Result<(), DFTD3Error> {
+        let cutoff = RealspaceCutoff::new_f(disp2, disp3, cn)?;
         let mut error = DFTD3Error::new();
         unsafe {
             ffi::dftd3_set_model_realspace_cutoff(error.get_c_ptr(), self.ptr, disp2, disp3, cn)
         };
         match error.check() {
             true => Err(error),
-            false => Ok(()),
+            false => {
+                self.cutoff.set(cutoff);
+                Ok(())
+            }
         }
     }
 
     /// Set realspace cutoffs (quantities in Bohr)
-    pub fn set_realspace_cutoff(&self, r0: f64, r1: f64, r2: f64) {
-        self.set_realspace_cutoff_f(r0, r1, r2).unwrap()
+    ///
+    /// # Arguments
+    ///
+    /// * `disp2` - cutoff of two-body dispersion
+    /// * `disp3` - cutoff of three-body dispersion
+    /// * `cn` - cutoff of coordination numbers
+    pub fn set_realspace_cutoff(&self, disp2: f64, disp3: f64, cn: f64) {
+        self.set_realspace_cutoff_f(disp2, disp3, cn).unwrap()
+    }
+
+    /// Apply realspace cutoffs (failable)
+    pub fn apply_realspace_cutoff_f(&self, cutoff: &RealspaceCutoff) -> Result<(), DFTD3Error> {
+        self.set_realspace_cutoff_f(cutoff.disp2, cutoff.disp3, cutoff.cn)
+    }
+
+    /// Apply realspace cutoffs
+    pub fn apply_realspace_cutoff(&self, cutoff: &RealspaceCutoff) {
+        self.apply_realspace_cutoff_f(cutoff).unwrap()
+    }
+
+    /// Get currently applied realspace cutoffs (quantities in Bohr)
+    pub fn get_realspace_cutoff(&self) -> RealspaceCutoff {
+        self.cutoff.get()
     }
 }
 
@@ -285,6 +512,10 @@ impl Drop for DFTD3Param {
     }
 }
 
+// The C handle is exclusively owned, and is read-only after creation.
+unsafe impl Send for DFTD3Param {}
+unsafe impl Sync for DFTD3Param {}
+
 impl DFTD3Param {
     /// Create new zero damping parameters (failable)
     pub fn new_zero_damping_f(
@@ -311,9 +542,11 @@ impl DFTD3Param {
 
     /// Load zero damping parameters from internal storage (failable)
     pub fn load_zero_damping_f(method: &str, atm: bool) -> Result<Self, DFTD3Error> {
+        let token = to_c_string(method)?;
         let mut error = DFTD3Error::new();
-        let token = std::ffi::CString::new(method).unwrap();
-        let ptr = unsafe { ffi::dftd3_load_zero_damping(error.get_c_ptr(), token.into_raw(), atm) };
+        let ptr = unsafe {
+            ffi::dftd3_load_zero_damping(error.get_c_ptr(), token.as_ptr() as *mut c_char, atm)
+        };
         match error.check() {
             true => Err(error),
             false => Ok(Self { ptr }),
@@ -350,10 +583,11 @@ impl DFTD3Param {
 
     /// Load rational damping parameters from internal storage (failable)
     pub fn load_rational_damping_f(method: &str, atm: bool) -> Result<Self, DFTD3Error> {
+        let token = to_c_string(method)?;
         let mut error = DFTD3Error::new();
-        let token = std::ffi::CString::new(method).unwrap();
-        let ptr =
-            unsafe { ffi::dftd3_load_rational_damping(error.get_c_ptr(), token.into_raw(), atm) };
+        let ptr = unsafe {
+            ffi::dftd3_load_rational_damping(error.get_c_ptr(), token.as_ptr() as *mut c_char, atm)
+        };
         match error.check() {
             true => Err(error),
             false => Ok(Self { ptr }),
@@ -400,10 +634,11 @@ impl DFTD3Param {
 
     /// Load modified zero damping parameters from internal storage (failable)
     pub fn load_mzero_damping_f(method: &str, atm: bool) -> Result<Self, DFTD3Error> {
+        let token = to_c_string(method)?;
         let mut error = DFTD3Error::new();
-        let token = std::ffi::CString::new(method).unwrap();
-        let ptr =
-            unsafe { ffi::dftd3_load_mzero_damping(error.get_c_ptr(), token.into_raw(), atm) };
+        let ptr = unsafe {
+            ffi::dftd3_load_mzero_damping(error.get_c_ptr(), token.as_ptr() as *mut c_char, atm)
+        };
         match error.check() {
             true => Err(error),
             false => Ok(Self { ptr }),
@@ -440,10 +675,11 @@ impl DFTD3Param {
 
     /// Load modified rational damping parameters from internal storage (failable)
     pub fn load_mrational_damping_f(method: &str, atm: bool) -> Result<Self, DFTD3Error> {
+        let token = to_c_string(method)?;
         let mut error = DFTD3Error::new();
-        let token = std::ffi::CString::new(method).unwrap();
-        let ptr =
-            unsafe { ffi::dftd3_load_mrational_damping(error.get_c_ptr(), token.into_raw(), atm) };
+        let ptr = unsafe {
+            ffi::dftd3_load_mrational_damping(error.get_c_ptr(), token.as_ptr() as *mut c_char, atm)
+        };
         match error.check() {
             true => Err(error),
             false => Ok(Self { ptr }),
@@ -490,10 +726,14 @@ impl DFTD3Param {
 
     /// Load optimized damping parameters from internal storage (failable)
     pub fn load_optimizedpower_damping_f(method: &str, atm: bool) -> Result<Self, DFTD3Error> {
+        let token = to_c_string(method)?;
         let mut error = DFTD3Error::new();
-        let token = std::ffi::CString::new(method).unwrap();
         let ptr = unsafe {
-            ffi::dftd3_load_optimizedpower_damping(error.get_c_ptr(), token.into_raw(), atm)
+            ffi::dftd3_load_optimizedpower_damping(
+                error.get_c_ptr(),
+                token.as_ptr() as *mut c_char,
+                atm,
+            )
         };
         match error.check() {
             true => Err(error),
@@ -505,6 +745,29 @@ impl DFTD3Param {
     pub fn load_optimizedpower_damping(method: &str, atm: bool) -> Self {
         Self::load_optimizedpower_damping_f(method, atm).unwrap()
     }
+
+    /// Load damping parameters of given damping kind from internal storage (failable)
+    ///
+    /// Damping kinds (case-insensitive) are `d3bj`/`rational`, `d3zero`/`zero`,
+    /// `d3bjm`/`mrational`, `d3zerom`/`mzero` and `d3op`/`optimizedpower`.
+    pub fn load_damping_f(damping: &str, method: &str, atm: bool) -> Result<Self, DFTD3Error> {
+        match damping.to_lowercase().as_str() {
+            "d3bj" | "rational" => Self::load_rational_damping_f(method, atm),
+            "d3zero" | "zero" => Self::load_zero_damping_f(method, atm),
+            "d3bjm" | "mrational" => Self::load_mrational_damping_f(method, atm),
+            "d3zerom" | "mzero" => Self::load_mzero_damping_f(method, atm),
+            "d3op" | "optimizedpower" => Self::load_optimizedpower_damping_f(method, atm),
+            _ => Err(DFTD3Error::Rust(format!(
+                "Unknown damping kind {}",
+                damping
+            ))),
+        }
+    }
+
+    /// Load damping parameters of given damping kind from internal storage
+    pub fn load_damping(damping: &str, method: &str, atm: bool) -> Self {
+        Self::load_damping_f(damping, method, atm).unwrap()
+    }
 }
 
 pub struct DFTD3GCP {
@@ -517,6 +780,9 @@ impl Drop for DFTD3GCP {
     }
 }
 
+// The C handle is exclusively owned; not `Sync`, since cutoffs are set by `&self`.
+unsafe impl Send for DFTD3GCP {}
+
 impl DFTD3GCP {
     /// Load geometric counter-poise parameters from internal storage (failable)
     pub fn load_gcp_param_f(
@@ -524,15 +790,15 @@ impl DFTD3GCP {
         method: &str,
         basis: &str,
     ) -> Result<Self, DFTD3Error> {
+        let token_method = to_c_string(method)?;
+        let token_basis = to_c_string(basis)?;
         let mut error = DFTD3Error::new();
-        let token_method = std::ffi::CString::new(method).unwrap();
-        let token_basis = std::ffi::CString::new(basis).unwrap();
         let ptr = unsafe {
             ffi::dftd3_load_gcp_param(
                 error.get_c_ptr(),
                 structure.ptr,
-                token_method.into_raw(),
-                token_basis.into_raw(),
+                token_method.as_ptr() as *mut c_char,
+                token_basis.as_ptr() as *mut c_char,
             )
         };
         match error.check() {
@@ -609,6 +875,55 @@ pub fn get_dispersion(
     get_dispersion_f(structure, model, param, eval_grad, eval_sigma).unwrap()
 }
 
+/// Result of dispersion evaluation
+///
+/// This is the typed counterpart of tuple returned by [`get_dispersion`]; conversion is
+/// performed by `get_dispersion(...).into()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DFTD3Output {
+    /// dispersion energy
+    pub energy: f64,
+    /// gradient [natoms][3]
+    pub gradient: Option<Vec<f64>>,
+    /// sigma (virial) [3][3]
+    pub sigma: Option<Vec<f64>>,
+}
+
+impl From<(f64, Option<Vec<f64>>, Option<Vec<f64>>)> for DFTD3Output {
+    fn from(result: (f64, Option<Vec<f64>>, Option<Vec<f64>>)) -> Self {
+        let (energy, gradient, sigma) = result;
+        Self {
+            energy,
+            gradient,
+            sigma,
+        }
+    }
+}
+
+impl DFTD3Output {
+    /// Get number of atoms (only available if gradient is evaluated)
+    pub fn get_natoms(&self) -> Option<usize> {
+        self.gradient.as_ref().map(|x| x.len() / 3)
+    }
+
+    /// Gradient as per-atom rows [natoms][3]
+    pub fn gradient_rows(&self) -> Option<Vec<[f64; 3]>> {
+        let gradient = self.gradient.as_ref()?;
+        Some(
+            gradient
+                .chunks_exact(3)
+                .map(|x| [x[0], x[1], x[2]])
+                .collect(),
+        )
+    }
+
+    /// Sigma as rows [3][3]
+    pub fn sigma_rows(&self) -> Option<[[f64; 3]; 3]> {
+        let s = self.sigma.as_ref()?;
+        Some([[s[0], s[1], s[2]], [s[3], s[4], s[5]], [s[6], s[7], s[8]]])
+    }
+}
+
 /// Evaluate the pairwise representation of the dispersion energy (failable)
 pub fn get_pairwise_dispersion_f(
     structure: &DFTD3Structure,
@@ -699,15 +1014,43 @@ mod tests {
         println!("Error check   : {}", error.check());
         println!("Error message : {}", error.get_message());
         let token = std::ffi::CString::new("Hello").unwrap();
-        unsafe { dftd3_load_optimizedpower_damping(error.get_c_ptr(), token.into_raw(), false) };
+        unsafe {
+            dftd3_load_optimizedpower_damping(
+                error.get_c_ptr(),
+                token.as_ptr() as *mut c_char,
+                false,
+            )
+        };
         println!("Error check   : {}", error.check());
         println!("Error message : {}", error.get_message());
         let token = std::ffi::CString::new("B3LYP").unwrap();
-        unsafe { dftd3_load_optimizedpower_damping(error.get_c_ptr(), token.into_raw(), false) };
+        unsafe {
+            dftd3_load_optimizedpower_damping(
+                error.get_c_ptr(),
+                token.as_ptr() as *mut c_char,
+                false,
+            )
+        };
         println!("Error check   : {}", error.check());
         println!("Error message : {}", error.get_message());
     }
 
+    #[test]
+    fn test_invalid_name() {
+        let error = DFTD3Param::load_rational_damping_f("b3\0lyp", false)
+            .err()
+            .unwrap();
+        assert!(matches!(error, DFTD3Error::InvalidString(_)));
+        assert!(error.get_message().contains("position 2"));
+
+        let structure =
+            DFTD3Structure::new(2, &[1, 1], &[0.0, 0.0, 0.0, 0.0, 0.0, 1.4], None, None);
+        let error = DFTD3GCP::load_gcp_param_f(&structure, "hf3c", "min\0ix")
+            .err()
+            .unwrap();
+        assert!(matches!(error, DFTD3Error::InvalidString(_)));
+    }
+
     #[test]
     fn test_get_dispersion() {
         let natoms = 2;
@@ -721,4 +1064,31 @@ mod tests {
         println!("Dispersion gradient: {:?}", grad);
         println!("Dispersion sigma: {:?}", sigma);
     }
+
+    #[test]
+    fn test_structure_accessors() {
+        let numbers = vec![8, 1, 1];
+        let positions = vec![0.0, 0.0, 0.0, 0.0, 0.0, 1.8, 1.7, 0.0, -0.5];
+        let structure = DFTD3Structure::new(3, &numbers, &positions, None, None);
+        assert_eq!(structure.get_numbers(), &numbers);
+        assert_eq!(structure.get_symbols(), vec!["O", "H", "H"]);
+        assert!(structure.get_lattice().is_none());
+
+        let moved = positions.iter().map(|x| x + 1.0).collect::<Vec<f64>>();
+        structure.update(&moved, None);
+        assert_eq!(structure.get_positions(), moved);
+        let center = structure.get_center_of_mass();
+        assert!(center[2] > 1.0 && center[2] < 1.2);
+
+        // clone has independent C handle with the same data
+        let cloned = structure.clone();
+        structure.update(&positions, None);
+        assert_eq!(cloned.get_positions(), moved);
+        let model = DFTD3Model::new(&cloned);
+        let param = DFTD3Param::load_rational_damping("B3LYP", false);
+        let e_cloned = get_dispersion(&cloned, &model, &param, false, false).0;
+        let model = DFTD3Model::new(&structure);
+        let e_orig = get_dispersion(&structure, &model, &param, false, false).0;
+        assert!((e_cloned - e_orig).abs() < 1e-12);
+    }
 }
@@ -0,0 +1,218 @@
+//! Ghost atoms and atom masking.
+//!
+//! Masked (ghost) atoms are excluded from dispersion without rebuilding the geometry: the active
+//! atoms are extracted into a sub-structure, evaluated with a new model of the same realspace
+//! cutoffs, and results are scattered back to the original atom indices. Ghost atoms contribute
+//! nothing to energy, and their gradients and pairwise energies are zero.
+
+use crate::prelude::*;
+
+/// Mask of atoms with index mapping from active atoms to the full structure
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtomMask {
+    natoms: usize,
+    /// indices of active (non-ghost) atoms in the full structure
+    active: Vec<usize>,
+}
+
+impl AtomMask {
+    /// Create mask from ghost flags [natoms], `true` for ghost atoms
+    pub fn from_ghosts(ghosts: &[bool]) -> Self {
+        let active = (0..ghosts.len()).filter(|&i| !ghosts[i]).collect();
+        Self {
+            natoms: ghosts.len(),
+            active,
+        }
+    }
+
+    /// Create mask from indices of active atoms (failable)
+    pub fn from_active_f(natoms: usize, active: &[usize]) -> Result<Self, DFTD3Error> {
+        if let Some(&idx) = active.iter().find(|&&idx| idx >= natoms) {
+            return Err(DFTD3Error::Rust(format!(
+                "Invalid atom index {}, number of atoms is {}",
+                idx, natoms
+            )));
+        }
+        let mut active = active.to_vec();
+        active.sort_unstable();
+        active.dedup();
+        Ok(Self { natoms, active })
+    }
+
+    /// Create mask from indices of active atoms
+    pub fn from_active(natoms: usize, active: &[usize]) -> Self {
+        Self::from_active_f(natoms, active).unwrap()
+    }
+
+    /// Get number of atoms of the full structure
+    pub fn get_natoms(&self) -> usize {
+        self.natoms
+    }
+
+    /// Get indices of active atoms in the full structure
+    pub fn get_active(&self) -> &[usize] {
+        &self.active
+    }
+
+    /// Whether atom `i` of the full structure is a ghost
+    pub fn is_ghost(&self, i: usize) -> bool {
+        self.active.binary_search(&i).is_err()
+    }
+
+    fn check_structure_f(&self, structure: &DFTD3Structure) -> Result<(), DFTD3Error> {
+        if structure.get_natoms() != self.natoms {
+            return Err(DFTD3Error::Rust(format!(
+                "Invalid dimension for mask, expected {}, got {}",
+                structure.get_natoms(),
+                self.natoms
+            )));
+        }
+        Ok(())
+    }
+
+    /// Scatter per-atom vectors [nactive][3] of active atoms to the full structure [natoms][3]
+    pub fn scatter_gradient(&self, gradient: &[f64]) -> Vec<f64> {
+        let mut result = vec![0.0; 3 * self.natoms];
+        for (n, &i) in self.active.iter().enumerate() {
+            result[3 * i..3 * i + 3].copy_from_slice(&gradient[3 * n..3 * n + 3]);
+        }
+        result
+    }
+
+    /// Scatter pair matrix [nactive][nactive] of active atoms to the full structure
+    /// [natoms][natoms]
+    pub fn scatter_pairwise(&self, pair: &[f64]) -> Vec<f64> {
+        let (natoms, nactive) = (self.natoms, self.active.len());
+        let mut result = vec![0.0; natoms * natoms];
+        for (n, &i) in self.active.iter().enumerate() {
+            for (m, &j) in self.active.iter().enumerate() {
+                result[i * natoms + j] = pair[n * nactive + m];
+            }
+        }
+        result
+    }
+}
+
+/// Sub-structure of active atoms and its model with the realspace cutoffs of `model`.
+fn extract_active_f(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    mask: &AtomMask,
+) -> Result<(DFTD3Structure, DFTD3Model), DFTD3Error> {
+    mask.check_structure_f(structure)?;
+    let active = structure.extract_f(mask.get_active())?;
+    let active_model = DFTD3Model::new_f(&active)?;
+    active_model.apply_realspace_cutoff_f(&model.get_realspace_cutoff())?;
+    Ok((active, active_model))
+}
+
+/// Evaluate the dispersion energy and its derivatives with ghost atoms excluded (failable)
+///
+/// Output is the typed [`DFTD3Output`] for the full structure; gradient of ghost atoms is zero.
+pub fn get_dispersion_masked_f(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    mask: &AtomMask,
+    eval_grad: bool,
+    eval_sigma: bool,
+) -> Result<DFTD3Output, DFTD3Error> {
+    if mask.get_active().is_empty() {
+        mask.check_structure_f(structure)?;
+        let grad = eval_grad.then(|| vec![0.0; 3 * mask.get_natoms()]);
+        let sigma = eval_sigma.then(|| vec![0.0; 9]);
+        return Ok((0.0, grad, sigma).into());
+    }
+    let (active, active_model) = extract_active_f(structure, model, mask)?;
+    let (energy, grad, sigma) =
+        get_dispersion_f(&active, &active_model, param, eval_grad, eval_sigma)?;
+    Ok((energy, grad.map(|x| mask.scatter_gradient(&x)), sigma).into())
+}
+
+/// Evaluate the dispersion energy and its derivatives with ghost atoms excluded
+pub fn get_dispersion_masked(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    mask: &AtomMask,
+    eval_grad: bool,
+    eval_sigma: bool,
+) -> DFTD3Output {
+    get_dispersion_masked_f(structure, model, param, mask, eval_grad, eval_sigma).unwrap()
+}
+
+/// Evaluate the pairwise representation of the dispersion energy with ghost atoms excluded
+/// (failable)
+///
+/// Pair energies [natoms][natoms] involving ghost atoms are zero.
+pub fn get_pairwise_dispersion_masked_f(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    mask: &AtomMask,
+) -> Result<(Vec<f64>, Vec<f64>), DFTD3Error> {
+    if mask.get_active().is_empty() {
+        mask.check_structure_f(structure)?;
+        let natoms = mask.get_natoms();
+        return Ok((vec![0.0; natoms * natoms], vec![0.0; natoms * natoms]));
+    }
+    let (active, active_model) = extract_active_f(structure, model, mask)?;
+    let (pair_energy2, pair_energy3) = get_pairwise_dispersion_f(&active, &active_model, param)?;
+    Ok((
+        mask.scatter_pairwise(&pair_energy2),
+        mask.scatter_pairwise(&pair_energy3),
+    ))
+}
+
+/// Evaluate the pairwise representation of the dispersion energy with ghost atoms excluded
+pub fn get_pairwise_dispersion_masked(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    mask: &AtomMask,
+) -> (Vec<f64>, Vec<f64>) {
+    get_pairwise_dispersion_masked_f(structure, model, param, mask).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ghost_atoms() {
+        // water dimer (O H H) x 2, second molecule as ghost
+        #[rustfmt::skip]
+        let positions = [
+            0.000, 0.000, 0.000,   1.810, 0.000, 0.000,  -0.450, 1.750, 0.000,
+            5.600, 0.000, 0.000,   6.200, 1.700, 0.000,   6.200,-1.700, 0.000,
+        ];
+        let numbers = [8, 1, 1, 8, 1, 1];
+        let ghosts = [false, false, false, true, true, true];
+        let structure = DFTD3Structure::new(6, &numbers, &positions, None, None);
+        let model = DFTD3Model::new(&structure);
+        let param = DFTD3Param::load_rational_damping("B3LYP", true);
+        let mask = AtomMask::from_ghosts(&ghosts);
+        assert!(mask.is_ghost(4) && !mask.is_ghost(1));
+
+        let output = get_dispersion_masked(&structure, &model, &param, &mask, true, false);
+        let (energy, grad) = (output.energy, output.gradient.unwrap());
+        assert!(grad[9..].iter().all(|&x| x == 0.0));
+
+        // reference by monomer structure
+        let monomer = DFTD3Structure::new(3, &numbers[..3], &positions[..9], None, None);
+        let monomer_model = DFTD3Model::new(&monomer);
+        let (reference, grad_ref, _) =
+            get_dispersion(&monomer, &monomer_model, &param, true, false);
+        assert!((energy - reference).abs() < 1e-12);
+        let diff = grad[..9]
+            .iter()
+            .zip(grad_ref.unwrap())
+            .map(|(a, b)| (a - b).abs());
+        assert!(diff.fold(0.0, f64::max) < 1e-12);
+
+        let (pair2, pair3) = get_pairwise_dispersion_masked(&structure, &model, &param, &mask);
+        let pairwise = PairwiseDispersion::from_vecs(6, pair2, pair3);
+        assert!(pairwise.check_energy(energy, 1e-10));
+        assert_eq!(pairwise[(0, 4)], 0.0);
+    }
+}
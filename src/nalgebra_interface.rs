@@ -0,0 +1,110 @@
+//! Interface to `nalgebra` (feature `nalgebra`).
+//!
+//! Positions and gradients are per-atom `Vector3<f64>`. Lattice and sigma are `Matrix3<f64>`,
+//! where element `(i, j)` corresponds to `[i][j]` of the row-major layout used by the C library;
+//! so each row of lattice matrix is a lattice vector.
+
+use crate::prelude::*;
+use nalgebra::{Matrix3, Vector3};
+
+fn to_rows(positions: &[Vector3<f64>]) -> Vec<[f64; 3]> {
+    positions.iter().map(|x| [x.x, x.y, x.z]).collect()
+}
+
+fn matrix_to_rows(matrix: &Matrix3<f64>) -> [[f64; 3]; 3] {
+    [0, 1, 2].map(|i| [0, 1, 2].map(|j| matrix[(i, j)]))
+}
+
+impl DFTD3Structure {
+    /// Create new molecular structure data from `nalgebra` (quantities in Bohr) (failable)
+    pub fn from_nalgebra_f(
+        numbers: &[usize],
+        positions: &[Vector3<f64>],
+        lattice: Option<&Matrix3<f64>>,
+        periodic: Option<&[bool]>,
+    ) -> Result<Self, DFTD3Error> {
+        let lattice = lattice.map(matrix_to_rows);
+        Self::from_rows_f(numbers, &to_rows(positions), lattice.as_ref(), periodic)
+    }
+
+    /// Create new molecular structure data from `nalgebra` (quantities in Bohr)
+    ///
+    /// # Arguments
+    ///
+    /// * `numbers` - numbers [natoms]
+    /// * `positions` - positions [natoms]
+    /// * `lattice` - lattice, each row is a lattice vector
+    /// * `periodic` - periodic [3]
+    pub fn from_nalgebra(
+        numbers: &[usize],
+        positions: &[Vector3<f64>],
+        lattice: Option<&Matrix3<f64>>,
+        periodic: Option<&[bool]>,
+    ) -> Self {
+        Self::from_nalgebra_f(numbers, positions, lattice, periodic).unwrap()
+    }
+
+    /// Update coordinates and lattice parameters from `nalgebra` (quantities in Bohr) (failable)
+    pub fn update_nalgebra_f(
+        &self,
+        positions: &[Vector3<f64>],
+        lattice: Option<&Matrix3<f64>>,
+    ) -> Result<(), DFTD3Error> {
+        let lattice = lattice.map(matrix_to_rows);
+        self.update_rows_f(&to_rows(positions), lattice.as_ref())
+    }
+
+    /// Update coordinates and lattice parameters from `nalgebra` (quantities in Bohr)
+    pub fn update_nalgebra(&self, positions: &[Vector3<f64>], lattice: Option<&Matrix3<f64>>) {
+        self.update_nalgebra_f(positions, lattice).unwrap()
+    }
+}
+
+impl DFTD3Output {
+    /// Gradient as per-atom `Vector3<f64>`
+    pub fn gradient_nalgebra(&self) -> Option<Vec<Vector3<f64>>> {
+        let gradient = self.gradient_rows()?;
+        Some(gradient.into_iter().map(Vector3::from).collect())
+    }
+
+    /// Sigma as `Matrix3<f64>`
+    pub fn sigma_nalgebra(&self) -> Option<Matrix3<f64>> {
+        let sigma = self.sigma_rows()?;
+        Some(Matrix3::from_fn(|i, j| sigma[i][j]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nalgebra_roundtrip() {
+        let numbers = vec![1, 1, 8];
+        let positions = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.4),
+            Vector3::new(1.2, 0.3, 0.7),
+        ];
+        let flat = positions
+            .iter()
+            .flat_map(|x| [x.x, x.y, x.z])
+            .collect::<Vec<f64>>();
+        let param = DFTD3Param::load_rational_damping("B3LYP", false);
+
+        let structure = DFTD3Structure::new(3, &numbers, &flat, None, None);
+        let model = DFTD3Model::new(&structure);
+        let reference: DFTD3Output = get_dispersion(&structure, &model, &param, true, true).into();
+
+        let structure = DFTD3Structure::from_nalgebra(&numbers, &positions, None, None);
+        let model = DFTD3Model::new(&structure);
+        let output: DFTD3Output = get_dispersion(&structure, &model, &param, true, true).into();
+
+        assert!((reference.energy - output.energy).abs() < 1e-12);
+        let gradient = output.gradient_nalgebra().unwrap();
+        assert_eq!(gradient.len(), 3);
+        assert_eq!(gradient[2].y, reference.gradient.as_ref().unwrap()[7]);
+        let sigma = output.sigma_nalgebra().unwrap();
+        assert_eq!(sigma[(0, 1)], reference.sigma.as_ref().unwrap()[1]);
+    }
+}
@@ -0,0 +1,243 @@
+//! Interface to `ndarray` (feature `ndarray`).
+//!
+//! Positions, gradients and lattice are `[natoms, 3]` or `[3, 3]` arrays. Arrays in row-major
+//! (standard) layout are passed to the C library without copy; arrays in other layouts (column-major,
+//! strided or transposed views) are copied into row-major buffer first.
+
+use crate::prelude::*;
+use ndarray::prelude::*;
+use std::borrow::Cow;
+
+/// Result of dispersion evaluation as `ndarray`
+///
+/// This is the array counterpart of [`DFTD3Output`], obtained by [`DFTD3Output::into_arrays`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DFTD3ArrayOutput {
+    /// dispersion energy
+    pub energy: f64,
+    /// gradient [natoms, 3]
+    pub gradient: Option<Array2<f64>>,
+    /// sigma (virial) [3, 3]
+    pub sigma: Option<Array2<f64>>,
+}
+
+/// Obtain row-major data of 2-D array, copy only if the array is not in standard layout.
+fn as_row_major<'a>(arr: &'a ArrayView2<f64>) -> Cow<'a, [f64]> {
+    match arr.as_slice() {
+        Some(slice) => Cow::Borrowed(slice),
+        None => Cow::Owned(arr.iter().copied().collect()),
+    }
+}
+
+/// Check shape of 2-D array.
+fn check_shape(name: &str, arr: &ArrayView2<f64>, shape: [usize; 2]) -> Result<(), DFTD3Error> {
+    if arr.shape() != shape {
+        return Err(DFTD3Error::Rust(format!(
+            "Invalid dimension for {}, expected {:?}, got {:?}",
+            name,
+            shape,
+            arr.shape()
+        )));
+    }
+    Ok(())
+}
+
+impl DFTD3Structure {
+    /// Create new molecular structure data from `ndarray` (quantities in Bohr) (failable)
+    pub fn from_array_f(
+        numbers: &[usize],
+        positions: ArrayView2<f64>,
+        lattice: Option<ArrayView2<f64>>,
+        periodic: Option<&[bool]>,
+    ) -> Result<Self, DFTD3Error> {
+        let natoms = positions.nrows();
+        check_shape("positions", &positions, [natoms, 3])?;
+        if let Some(lattice) = &lattice {
+            check_shape("lattice", lattice, [3, 3])?;
+        }
+        let positions = as_row_major(&positions);
+        let lattice = lattice.as_ref().map(as_row_major);
+        Self::new_f(natoms, numbers, &positions, lattice.as_deref(), periodic)
+    }
+
+    /// Create new molecular structure data from `ndarray` (quantities in Bohr)
+    ///
+    /// # Arguments
+    ///
+    /// * `numbers` - numbers [natoms]
+    /// * `positions` - positions [natoms, 3]
+    /// * `lattice` - lattice [3, 3]
+    /// * `periodic` - periodic [3]
+    pub fn from_array(
+        numbers: &[usize],
+        positions: ArrayView2<f64>,
+        lattice: Option<ArrayView2<f64>>,
+        periodic: Option<&[bool]>,
+    ) -> Self {
+        Self::from_array_f(numbers, positions, lattice, periodic).unwrap()
+    }
+
+    /// Update coordinates and lattice parameters from `ndarray` (quantities in Bohr) (failable)
+    pub fn update_array_f(
+        &self,
+        positions: ArrayView2<f64>,
+        lattice: Option<ArrayView2<f64>>,
+    ) -> Result<(), DFTD3Error> {
+        check_shape("positions", &positions, [self.get_natoms(), 3])?;
+        if let Some(lattice) = &lattice {
+            check_shape("lattice", lattice, [3, 3])?;
+        }
+        let positions = as_row_major(&positions);
+        let lattice = lattice.as_ref().map(as_row_major);
+        self.update_f(&positions, lattice.as_deref())
+    }
+
+    /// Update coordinates and lattice parameters from `ndarray` (quantities in Bohr)
+    ///
+    /// # Arguments
+    ///
+    /// * `positions` - positions [natoms, 3]
+    /// * `lattice` - lattice [3, 3]
+    pub fn update_array(&self, positions: ArrayView2<f64>, lattice: Option<ArrayView2<f64>>) {
+        self.update_array_f(positions, lattice).unwrap()
+    }
+}
+
+impl DFTD3Output {
+    /// Gradient as view of shape [natoms, 3] (no copy)
+    pub fn gradient_view(&self) -> Option<ArrayView2<'_, f64>> {
+        let gradient = self.gradient.as_ref()?;
+        Some(ArrayView2::from_shape((gradient.len() / 3, 3), gradient).unwrap())
+    }
+
+    /// Sigma as view of shape [3, 3] (no copy)
+    pub fn sigma_view(&self) -> Option<ArrayView2<'_, f64>> {
+        let sigma = self.sigma.as_ref()?;
+        Some(ArrayView2::from_shape((3, 3), sigma).unwrap())
+    }
+
+    /// Convert to energy, gradient [natoms, 3] and sigma [3, 3] arrays (no copy)
+    pub fn into_arrays(self) -> DFTD3ArrayOutput {
+        let gradient = self.gradient.map(|x| {
+            let natoms = x.len() / 3;
+            Array2::from_shape_vec((natoms, 3), x).unwrap()
+        });
+        let sigma = self
+            .sigma
+            .map(|x| Array2::from_shape_vec((3, 3), x).unwrap());
+        DFTD3ArrayOutput {
+            energy: self.energy,
+            gradient,
+            sigma,
+        }
+    }
+}
+
+impl PairwiseDispersion {
+    /// Two-body and three-body pairwise energies as [natoms, natoms] arrays
+    pub fn to_arrays(&self) -> (Array2<f64>, Array2<f64>) {
+        let natoms = self.get_natoms();
+        (
+            pairwise_to_array(natoms, self.get_pair_energy2().to_vec()),
+            pairwise_to_array(natoms, self.get_pair_energy3().to_vec()),
+        )
+    }
+}
+
+/// Reshape flattened pairwise energy (from [`get_pairwise_dispersion`]) to [natoms, natoms] array (no copy)
+pub fn pairwise_to_array(natoms: usize, pair_energy: Vec<f64>) -> Array2<f64> {
+    Array2::from_shape_vec((natoms, natoms), pair_energy).unwrap()
+}
+
+/// Evaluate the dispersion energy and its derivatives, returning `ndarray` (failable)
+pub fn get_dispersion_array_f(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    eval_grad: bool,
+    eval_sigma: bool,
+) -> Result<DFTD3ArrayOutput, DFTD3Error> {
+    let output: DFTD3Output =
+        get_dispersion_f(structure, model, param, eval_grad, eval_sigma)?.into();
+    Ok(output.into_arrays())
+}
+
+/// Evaluate the dispersion energy and its derivatives, returning `ndarray`
+///
+/// Gradient is of shape [natoms, 3], and sigma is of shape [3, 3].
+pub fn get_dispersion_array(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    eval_grad: bool,
+    eval_sigma: bool,
+) -> DFTD3ArrayOutput {
+    get_dispersion_array_f(structure, model, param, eval_grad, eval_sigma).unwrap()
+}
+
+/// Evaluate the pairwise representation of the dispersion energy, returning `ndarray` (failable)
+pub fn get_pairwise_dispersion_array_f(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+) -> Result<(Array2<f64>, Array2<f64>), DFTD3Error> {
+    let natoms = structure.get_natoms();
+    let (pair_energy2, pair_energy3) = get_pairwise_dispersion_f(structure, model, param)?;
+    Ok((
+        pairwise_to_array(natoms, pair_energy2),
+        pairwise_to_array(natoms, pair_energy3),
+    ))
+}
+
+/// Evaluate the pairwise representation of the dispersion energy, returning `ndarray`
+///
+/// Both two-body and three-body pairwise energies are of shape [natoms, natoms].
+pub fn get_pairwise_dispersion_array(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+) -> (Array2<f64>, Array2<f64>) {
+    get_pairwise_dispersion_array_f(structure, model, param).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_array_layout() {
+        let numbers = vec![1, 1, 8];
+        let positions = array![[0.0, 0.0, 0.0], [0.0, 0.0, 1.4], [1.2, 0.3, 0.7]];
+        let param = DFTD3Param::load_rational_damping("B3LYP", false);
+
+        let structure = DFTD3Structure::from_array(&numbers, positions.view(), None, None);
+        let model = DFTD3Model::new(&structure);
+        let output_c = get_dispersion_array(&structure, &model, &param, true, false);
+
+        // column-major layout of the same positions
+        let positions_t = positions.t().as_standard_layout().into_owned();
+        let positions_f = positions_t.t();
+        assert!(positions_f.as_slice().is_none());
+        let structure = DFTD3Structure::from_array(&numbers, positions_f, None, None);
+        let model = DFTD3Model::new(&structure);
+        let output_f = get_dispersion_array(&structure, &model, &param, true, false);
+
+        let (grad_c, grad_f) = (output_c.gradient.unwrap(), output_f.gradient.unwrap());
+        assert!((output_c.energy - output_f.energy).abs() < 1e-12);
+        assert_eq!(grad_c.shape(), &[3, 3]);
+        assert!((grad_c - grad_f).iter().all(|x| x.abs() < 1e-12));
+    }
+
+    #[test]
+    fn test_pairwise_array() {
+        let numbers = vec![1, 1, 8];
+        let positions = array![[0.0, 0.0, 0.0], [0.0, 0.0, 1.4], [1.2, 0.3, 0.7]];
+        let structure = DFTD3Structure::from_array(&numbers, positions.view(), None, None);
+        let model = DFTD3Model::new(&structure);
+        let param = DFTD3Param::load_zero_damping("B3LYP", true);
+        let energy = get_dispersion_array(&structure, &model, &param, false, false).energy;
+        let (pair2, pair3) = get_pairwise_dispersion_array(&structure, &model, &param);
+        assert_eq!(pair2.shape(), &[3, 3]);
+        assert!((pair2.sum() + pair3.sum() - energy).abs() < 1e-10);
+    }
+}
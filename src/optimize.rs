@@ -0,0 +1,489 @@
+//! Geometry optimization of user-provided base energy plus dispersion.
+//!
+//! The minimized energy is `E_user(x) + E_D3(x)`, where the base energy and gradient (e.g. of a
+//! force field) come from a user closure taking the current structure. One [`DFTD3Structure`] and
+//! [`DFTD3Model`] are reused and updated by [`DFTD3Structure::update_f`] at each step. L-BFGS and
+//! FIRE are available.
+//!
+//! For variable-cell optimization of periodic structures, atoms and lattice are deformed together
+//! by `r = (1 + eps) u`, and the strain gradient is obtained from sigma. As in the unit cell filter
+//! of ASE, strain coordinates are scaled by the number of atoms. All quantities in atomic units.
+
+use crate::prelude::*;
+use crate::trajectory::write_extxyz_frame;
+use std::collections::VecDeque;
+use std::io::Write;
+
+/// Initial curvature (in Hartree/Bohr^2) of L-BFGS before curvature pairs are available
+const LBFGS_INITIAL_CURVATURE: f64 = 0.1;
+
+/// Energy, gradient [natoms][3] and optional sigma [3][3] of user-provided base potential
+pub type UserEnergy = (f64, Vec<f64>, Option<Vec<f64>>);
+
+/// Optimization algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Optimizer {
+    /// limited-memory BFGS with trust radius
+    LBFGS,
+    /// fast inertial relaxation engine
+    FIRE,
+}
+
+/// Options of geometry optimization
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptimizeOptions {
+    /// optimization algorithm
+    pub optimizer: Optimizer,
+    /// maximum number of steps
+    pub max_steps: usize,
+    /// convergence threshold of maximum atomic force (and scaled strain gradient), in Hartree/Bohr
+    pub fmax: f64,
+    /// maximum displacement of any atom per step (in Bohr)
+    pub max_step: f64,
+    /// number of stored curvature pairs of L-BFGS
+    pub lbfgs_memory: usize,
+    /// initial time step of FIRE
+    pub fire_dt: f64,
+    /// maximum time step of FIRE
+    pub fire_dt_max: f64,
+    /// optimize lattice together with positions (periodic structures only)
+    pub variable_cell: bool,
+    /// store positions and lattice of each step in the result
+    pub trajectory: bool,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self {
+            optimizer: Optimizer::LBFGS,
+            max_steps: 500,
+            fmax: 4.5e-4,
+            max_step: 0.2,
+            lbfgs_memory: 20,
+            fire_dt: 1.0,
+            fire_dt_max: 10.0,
+            variable_cell: false,
+            trajectory: false,
+        }
+    }
+}
+
+/// One step of optimization trajectory
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptimizeFrame {
+    /// total energy
+    pub energy: f64,
+    /// positions [natoms][3]
+    pub positions: Vec<f64>,
+    /// lattice [3][3], if periodic
+    pub lattice: Option<Vec<f64>>,
+}
+
+/// Result of geometry optimization
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptimizeResult {
+    /// total energy at final geometry
+    pub energy: f64,
+    /// total gradient [natoms][3] at final geometry
+    pub gradient: Vec<f64>,
+    /// total sigma [3][3] at final geometry (variable-cell optimization only)
+    pub sigma: Option<Vec<f64>>,
+    /// whether convergence criterion was met
+    pub converged: bool,
+    /// number of performed steps
+    pub nsteps: usize,
+    /// initial and accepted geometries, if requested
+    pub trajectory: Vec<OptimizeFrame>,
+}
+
+/// Energy and gradient in optimization coordinates, with cartesian gradient and sigma
+struct Evaluation {
+    energy: f64,
+    gradient: Vec<f64>,
+    cartesian: Vec<f64>,
+    sigma: Option<Vec<f64>>,
+}
+
+/// Objective in optimization coordinates `q = [u, natoms * eps]`
+struct Objective<'a, F> {
+    structure: &'a DFTD3Structure,
+    model: &'a DFTD3Model,
+    param: &'a DFTD3Param,
+    user: F,
+    // reference lattice of variable-cell optimization
+    lattice: Option<Vec<f64>>,
+    cell_factor: f64,
+}
+
+/// Product of 3x3 matrix (row-major) and vector
+fn matvec(m: &[f64], v: &[f64]) -> [f64; 3] {
+    [0, 1, 2].map(|a| (0..3).map(|b| m[3 * a + b] * v[b]).sum())
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+impl<F> Objective<'_, F>
+where
+    F: FnMut(&DFTD3Structure) -> Result<UserEnergy, DFTD3Error>,
+{
+    fn deformation(&self, q: &[f64]) -> Vec<f64> {
+        let n = 3 * self.structure.get_natoms();
+        (0..9)
+            .map(|ab| (ab % 4 == 0) as usize as f64 + q[n + ab] / self.cell_factor)
+            .collect()
+    }
+
+    fn evaluate(&mut self, q: &[f64]) -> Result<Evaluation, DFTD3Error> {
+        let n = 3 * self.structure.get_natoms();
+        match &self.lattice {
+            None => self.structure.update_f(q, None)?,
+            Some(lattice0) => {
+                let d = self.deformation(q);
+                let positions = q[..n]
+                    .chunks(3)
+                    .flat_map(|u| matvec(&d, u))
+                    .collect::<Vec<_>>();
+                let lattice = lattice0
+                    .chunks(3)
+                    .flat_map(|a| matvec(&d, a))
+                    .collect::<Vec<_>>();
+                self.structure.update_f(&positions, Some(&lattice))?;
+            }
+        }
+        let eval_sigma = self.lattice.is_some();
+        let (energy, gradient, sigma) =
+            get_dispersion_f(self.structure, self.model, self.param, true, eval_sigma)?;
+        let (user_energy, user_gradient, user_sigma) = (self.user)(self.structure)?;
+        if user_gradient.len() != n {
+            return Err(DFTD3Error::Rust(format!(
+                "Invalid dimension for user gradient, expected {}, got {}",
+                n,
+                user_gradient.len()
+            )));
+        }
+        let cartesian = gradient
+            .unwrap()
+            .iter()
+            .zip(&user_gradient)
+            .map(|(a, b)| a + b)
+            .collect::<Vec<f64>>();
+        let energy = energy + user_energy;
+        if self.lattice.is_none() {
+            return Ok(Evaluation {
+                energy,
+                gradient: cartesian.clone(),
+                cartesian,
+                sigma: None,
+            });
+        }
+
+        // sigma of user potential is treated as zero if not given
+        let mut sigma = sigma.unwrap();
+        if let Some(user_sigma) = user_sigma {
+            sigma.iter_mut().zip(&user_sigma).for_each(|(a, b)| *a += b);
+        }
+        let d = self.deformation(q);
+        let d_inv = crate::stress::inverse(&d)
+            .ok_or_else(|| DFTD3Error::Rust("Singular cell deformation".to_string()))?;
+        // dE/du = D^T g, dE/d eps = sigma D^-T
+        let mut gradient = cartesian
+            .chunks(3)
+            .flat_map(|g| [0, 1, 2].map(|b| (0..3).map(|a| d[3 * a + b] * g[a]).sum::<f64>()))
+            .collect::<Vec<f64>>();
+        gradient.extend((0..9).map(|ac| {
+            let (a, c) = (ac / 3, ac % 3);
+            (0..3)
+                .map(|b| sigma[3 * a + b] * d_inv[3 * c + b])
+                .sum::<f64>()
+                / self.cell_factor
+        }));
+        Ok(Evaluation {
+            energy,
+            gradient,
+            cartesian,
+            sigma: Some(sigma),
+        })
+    }
+
+    fn frame(&self, energy: f64) -> OptimizeFrame {
+        OptimizeFrame {
+            energy,
+            positions: self.structure.get_positions(),
+            lattice: self.structure.get_lattice(),
+        }
+    }
+}
+
+/// Maximum norm of 3-vectors of atoms (and rows of strain)
+fn max_norm(v: &[f64]) -> f64 {
+    v.chunks(3).map(|x| dot(x, x).sqrt()).fold(0.0, f64::max)
+}
+
+/// Scale step so that no atom moves farther than `max_step`
+fn limit_step(step: &mut [f64], max_step: f64) {
+    let norm = max_norm(step);
+    if norm > max_step {
+        step.iter_mut().for_each(|x| *x *= max_step / norm);
+    }
+}
+
+/// Minimize user-provided energy plus dispersion (failable)
+///
+/// # Arguments
+///
+/// * `structure` - initial geometry; updated to the final geometry
+/// * `user` - closure returning energy, gradient [natoms][3] and optional sigma [3][3] of the base
+///   potential at the current structure
+///
+/// See also [`OptimizeOptions`].
+pub fn optimize_f<F>(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    options: &OptimizeOptions,
+    user: F,
+) -> Result<OptimizeResult, DFTD3Error>
+where
+    F: FnMut(&DFTD3Structure) -> Result<UserEnergy, DFTD3Error>,
+{
+    let natoms = structure.get_natoms();
+    let lattice = match options.variable_cell {
+        false => None,
+        true => Some(structure.get_lattice().ok_or_else(|| {
+            DFTD3Error::Rust("Variable-cell optimization requires lattice".to_string())
+        })?),
+    };
+    let mut q = structure.get_positions();
+    if lattice.is_some() {
+        q.extend([0.0; 9]);
+    }
+    let mut objective = Objective {
+        structure,
+        model,
+        param,
+        user,
+        lattice,
+        cell_factor: natoms as f64,
+    };
+
+    let mut trajectory = vec![];
+    let mut current = objective.evaluate(&q)?;
+    if options.trajectory {
+        trajectory.push(objective.frame(current.energy));
+    }
+    let converged = |eval: &Evaluation| max_norm(&eval.gradient) < options.fmax;
+    let mut nsteps = 0;
+
+    match options.optimizer {
+        Optimizer::LBFGS => {
+            let mut memory: VecDeque<(Vec<f64>, Vec<f64>, f64)> = VecDeque::new();
+            let mut trust = options.max_step;
+            while nsteps < options.max_steps && !converged(&current) {
+                nsteps += 1;
+                // two-loop recursion for -H g
+                let mut d = current.gradient.iter().map(|x| -x).collect::<Vec<f64>>();
+                let mut alphas = vec![];
+                for (s, y, rho) in memory.iter().rev() {
+                    let alpha = rho * dot(s, &d);
+                    d.iter_mut().zip(y).for_each(|(x, y)| *x -= alpha * y);
+                    alphas.push(alpha);
+                }
+                let gamma = memory
+                    .back()
+                    .map_or(1.0 / LBFGS_INITIAL_CURVATURE, |(s, y, _)| {
+                        dot(s, y) / dot(y, y)
+                    });
+                d.iter_mut().for_each(|x| *x *= gamma);
+                for ((s, y, rho), alpha) in memory.iter().zip(alphas.iter().rev()) {
+                    let beta = rho * dot(y, &d);
+                    d.iter_mut()
+                        .zip(s)
+                        .for_each(|(x, s)| *x += (alpha - beta) * s);
+                }
+                if dot(&d, &current.gradient) >= 0.0 {
+                    memory.clear();
+                    d = current
+                        .gradient
+                        .iter()
+                        .map(|x| -x / LBFGS_INITIAL_CURVATURE)
+                        .collect();
+                }
+                limit_step(&mut d, trust);
+
+                let q_new = q.iter().zip(&d).map(|(a, b)| a + b).collect::<Vec<f64>>();
+                let next = objective.evaluate(&q_new)?;
+                if next.energy > current.energy {
+                    // reject uphill step, restore geometry and restart from steepest descent
+                    memory.clear();
+                    trust *= 0.5;
+                    current = objective.evaluate(&q)?;
+                    continue;
+                }
+                let y = next
+                    .gradient
+                    .iter()
+                    .zip(&current.gradient)
+                    .map(|(a, b)| a - b)
+                    .collect::<Vec<f64>>();
+                let sy = dot(&d, &y);
+                if sy > f64::EPSILON {
+                    memory.push_back((d, y, 1.0 / sy));
+                    if memory.len() > options.lbfgs_memory {
+                        memory.pop_front();
+                    }
+                }
+                trust = (2.0 * trust).min(options.max_step);
+                q = q_new;
+                current = next;
+                if options.trajectory {
+                    trajectory.push(objective.frame(current.energy));
+                }
+            }
+        }
+        Optimizer::FIRE => {
+            let (n_min, f_inc, f_dec, alpha_start, f_alpha) = (5, 1.1, 0.5, 0.1, 0.99);
+            let mut velocity = vec![0.0; q.len()];
+            let mut dt = options.fire_dt;
+            let mut alpha = alpha_start;
+            let mut npositive = 0;
+            while nsteps < options.max_steps && !converged(&current) {
+                nsteps += 1;
+                let force = current.gradient.iter().map(|x| -x).collect::<Vec<f64>>();
+                if dot(&force, &velocity) > 0.0 {
+                    let ratio = (dot(&velocity, &velocity) / dot(&force, &force)).sqrt();
+                    velocity
+                        .iter_mut()
+                        .zip(&force)
+                        .for_each(|(v, f)| *v = (1.0 - alpha) * *v + alpha * ratio * f);
+                    npositive += 1;
+                    if npositive > n_min {
+                        dt = (dt * f_inc).min(options.fire_dt_max);
+                        alpha *= f_alpha;
+                    }
+                } else {
+                    velocity.iter_mut().for_each(|v| *v = 0.0);
+                    dt *= f_dec;
+                    alpha = alpha_start;
+                    npositive = 0;
+                }
+                velocity
+                    .iter_mut()
+                    .zip(&force)
+                    .for_each(|(v, f)| *v += dt * f);
+                let mut d = velocity.iter().map(|v| dt * v).collect::<Vec<f64>>();
+                limit_step(&mut d, options.max_step);
+                q.iter_mut().zip(&d).for_each(|(a, b)| *a += b);
+                current = objective.evaluate(&q)?;
+                if options.trajectory {
+                    trajectory.push(objective.frame(current.energy));
+                }
+            }
+        }
+    }
+
+    Ok(OptimizeResult {
+        energy: current.energy,
+        converged: converged(&current),
+        gradient: current.cartesian,
+        sigma: current.sigma,
+        nsteps,
+        trajectory,
+    })
+}
+
+/// Minimize user-provided energy plus dispersion
+///
+/// See also [`optimize_f`].
+pub fn optimize<F>(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    options: &OptimizeOptions,
+    user: F,
+) -> OptimizeResult
+where
+    F: FnMut(&DFTD3Structure) -> Result<UserEnergy, DFTD3Error>,
+{
+    optimize_f(structure, model, param, options, user).unwrap()
+}
+
+/// Write optimization trajectory in extended XYZ format (energy in eV) (failable)
+///
+/// `periodic` is written as `pbc` of frames with lattice (periodic in all directions if not given).
+pub fn write_optimize_trajectory_f(
+    mut writer: impl Write,
+    numbers: &[usize],
+    periodic: Option<&[bool]>,
+    trajectory: &[OptimizeFrame],
+) -> Result<(), DFTD3Error> {
+    for frame in trajectory {
+        let properties = format!("energy={:.12}", frame.energy * HARTREE_TO_EV);
+        let lattice = frame.lattice.as_deref();
+        write_extxyz_frame(
+            &mut writer,
+            numbers,
+            &frame.positions,
+            lattice,
+            periodic,
+            &properties,
+        )
+        .map_err(|err| DFTD3Error::Rust(format!("Trajectory I/O error: {}", err)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_optimize_restrained_dimer() {
+        // water dimer restrained harmonically to its initial geometry
+        #[rustfmt::skip]
+        let positions = [
+            0.000, 0.000, 0.000,   1.810, 0.000, 0.000,  -0.450, 1.750, 0.000,
+            5.600, 0.000, 0.000,   6.200, 1.700, 0.000,   6.200,-1.700, 0.000,
+        ];
+        let numbers = [8, 1, 1, 8, 1, 1];
+        let param = DFTD3Param::load_rational_damping("B3LYP", false);
+        let restraint = |structure: &DFTD3Structure| {
+            let k = 0.5;
+            let gradient = structure
+                .get_positions()
+                .iter()
+                .zip(&positions)
+                .map(|(x, x0)| k * (x - x0))
+                .collect::<Vec<f64>>();
+            let energy = dot(&gradient, &gradient) / (2.0 * k);
+            Ok((energy, gradient, None))
+        };
+
+        for optimizer in [Optimizer::LBFGS, Optimizer::FIRE] {
+            let structure = DFTD3Structure::new(6, &numbers, &positions, None, None);
+            let model = DFTD3Model::new(&structure);
+            let (energy0, _, _) = get_dispersion(&structure, &model, &param, false, false);
+            let options = OptimizeOptions {
+                optimizer,
+                fmax: 1e-5,
+                trajectory: true,
+                ..Default::default()
+            };
+            let result = optimize(&structure, &model, &param, &options, restraint);
+            assert!(result.converged);
+            assert!(result.energy < energy0);
+            assert!(max_norm(&result.gradient) < 1e-5);
+            assert!(result.trajectory.len() <= result.nsteps + 1);
+            assert_eq!(
+                result.trajectory.last().unwrap().positions,
+                structure.get_positions()
+            );
+
+            let mut output = vec![];
+            write_optimize_trajectory_f(&mut output, &numbers, None, &result.trajectory).unwrap();
+            let output = String::from_utf8(output).unwrap();
+            assert_eq!(output.lines().count(), 8 * result.trajectory.len());
+        }
+    }
+}
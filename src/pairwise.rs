@@ -0,0 +1,218 @@
+//! Pairwise representation of dispersion energy and its decomposition.
+
+use crate::prelude::*;
+
+/// Pairwise representation of dispersion energy
+///
+/// The pairwise energies from [`get_pairwise_dispersion`] are additive: each pair energy is split
+/// between `(i, j)` and `(j, i)`, and the sum over all elements reproduces the dispersion energy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairwiseDispersion {
+    natoms: usize,
+    /// two-body pairwise energy [natoms][natoms]
+    pair_energy2: Vec<f64>,
+    /// three-body pairwise energy [natoms][natoms]
+    pair_energy3: Vec<f64>,
+    /// sum of two-body and three-body pairwise energy [natoms][natoms]
+    pair_energy: Vec<f64>,
+}
+
+impl std::ops::Index<(usize, usize)> for PairwiseDispersion {
+    type Output = f64;
+
+    /// Total (two-body and three-body) pairwise energy of `(i, j)`
+    fn index(&self, index: (usize, usize)) -> &f64 {
+        let (i, j) = index;
+        assert!(i < self.natoms && j < self.natoms, "Index out of bound");
+        &self.pair_energy[i * self.natoms + j]
+    }
+}
+
+impl PairwiseDispersion {
+    /// Create pairwise dispersion from flattened two-body and three-body energies (failable)
+    pub fn from_vecs_f(
+        natoms: usize,
+        pair_energy2: Vec<f64>,
+        pair_energy3: Vec<f64>,
+    ) -> Result<Self, DFTD3Error> {
+        for (name, pair) in [
+            ("pair_energy2", &pair_energy2),
+            ("pair_energy3", &pair_energy3),
+        ] {
+            if pair.len() != natoms * natoms {
+                return Err(DFTD3Error::Rust(format!(
+                    "Invalid dimension for {}, expected {}, got {}",
+                    name,
+                    natoms * natoms,
+                    pair.len()
+                )));
+            }
+        }
+        let pair_energy = pair_energy2
+            .iter()
+            .zip(pair_energy3.iter())
+            .map(|(e2, e3)| e2 + e3)
+            .collect();
+        Ok(Self {
+            natoms,
+            pair_energy2,
+            pair_energy3,
+            pair_energy,
+        })
+    }
+
+    /// Create pairwise dispersion from flattened two-body and three-body energies
+    pub fn from_vecs(natoms: usize, pair_energy2: Vec<f64>, pair_energy3: Vec<f64>) -> Self {
+        Self::from_vecs_f(natoms, pair_energy2, pair_energy3).unwrap()
+    }
+
+    /// Evaluate the pairwise representation of the dispersion energy (failable)
+    pub fn new_f(
+        structure: &DFTD3Structure,
+        model: &DFTD3Model,
+        param: &DFTD3Param,
+    ) -> Result<Self, DFTD3Error> {
+        let (pair_energy2, pair_energy3) = get_pairwise_dispersion_f(structure, model, param)?;
+        Self::from_vecs_f(structure.get_natoms(), pair_energy2, pair_energy3)
+    }
+
+    /// Evaluate the pairwise representation of the dispersion energy
+    pub fn new(structure: &DFTD3Structure, model: &DFTD3Model, param: &DFTD3Param) -> Self {
+        Self::new_f(structure, model, param).unwrap()
+    }
+
+    /// Get number of atoms
+    pub fn get_natoms(&self) -> usize {
+        self.natoms
+    }
+
+    /// Two-body pairwise energy [natoms][natoms]
+    pub fn get_pair_energy2(&self) -> &[f64] {
+        &self.pair_energy2
+    }
+
+    /// Three-body pairwise energy [natoms][natoms]
+    pub fn get_pair_energy3(&self) -> &[f64] {
+        &self.pair_energy3
+    }
+
+    /// Two-body pairwise energy of `(i, j)`
+    pub fn two_body(&self, i: usize, j: usize) -> f64 {
+        self.pair_energy2[i * self.natoms + j]
+    }
+
+    /// Three-body pairwise energy of `(i, j)`
+    pub fn three_body(&self, i: usize, j: usize) -> f64 {
+        self.pair_energy3[i * self.natoms + j]
+    }
+
+    /// Total dispersion energy (sum of all pairwise energies)
+    pub fn energy(&self) -> f64 {
+        self.pair_energy.iter().sum()
+    }
+
+    /// Check whether the sum of pairwise energies reproduces dispersion energy (e.g. from
+    /// [`get_dispersion`]) within tolerance
+    pub fn check_energy(&self, energy: f64, tol: f64) -> bool {
+        (self.energy() - energy).abs() < tol
+    }
+
+    /// Per-atom partition of dispersion energy [natoms]
+    ///
+    /// Atomic energy is half of the row and column sums, so that the partition is valid regardless
+    /// of whether the pair energy matrix is stored symmetrically.
+    pub fn atom_partition(&self) -> Vec<f64> {
+        let n = self.natoms;
+        (0..n)
+            .map(|i| (0..n).map(|j| 0.5 * (self[(i, j)] + self[(j, i)])).sum())
+            .collect()
+    }
+
+    /// Fragment-fragment interaction matrix [nfrag][nfrag] (failable)
+    ///
+    /// `fragments` maps each atom to fragment index, and `nfrag` is the maximum fragment index plus
+    /// one. Diagonal element is the intra-fragment energy, and off-diagonal element `(a, b)` is the
+    /// full interaction energy between fragments `a` and `b`. So the dispersion energy is the sum
+    /// of the upper triangle (including diagonal).
+    pub fn fragment_matrix_f(&self, fragments: &[usize]) -> Result<Vec<f64>, DFTD3Error> {
+        let n = self.natoms;
+        if fragments.len() != n {
+            return Err(DFTD3Error::Rust(format!(
+                "Invalid dimension for fragments, expected {}, got {}",
+                n,
+                fragments.len()
+            )));
+        }
+        let nfrag = fragments.iter().max().map_or(0, |&x| x + 1);
+        let mut matrix = vec![0.0; nfrag * nfrag];
+        for i in 0..n {
+            for j in 0..n {
+                let (a, b) = (fragments[i], fragments[j]);
+                if a == b {
+                    matrix[a * nfrag + a] += self[(i, j)];
+                } else {
+                    matrix[a * nfrag + b] += self[(i, j)];
+                    matrix[b * nfrag + a] += self[(i, j)];
+                }
+            }
+        }
+        Ok(matrix)
+    }
+
+    /// Fragment-fragment interaction matrix [nfrag][nfrag]
+    pub fn fragment_matrix(&self, fragments: &[usize]) -> Vec<f64> {
+        self.fragment_matrix_f(fragments).unwrap()
+    }
+
+    /// Strongest `n` atom-atom contacts, as `(i, j, energy)` with `i < j`
+    ///
+    /// Energy of contact is `E(i, j) + E(j, i)`; contacts are sorted by descending magnitude.
+    pub fn strongest_contacts(&self, n: usize) -> Vec<(usize, usize, f64)> {
+        let natoms = self.natoms;
+        let mut contacts = (0..natoms)
+            .flat_map(|i| (i + 1..natoms).map(move |j| (i, j)))
+            .map(|(i, j)| (i, j, self[(i, j)] + self[(j, i)]))
+            .collect::<Vec<_>>();
+        contacts.sort_by(|a, b| b.2.abs().total_cmp(&a.2.abs()));
+        contacts.truncate(n);
+        contacts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pairwise_decomposition() {
+        // two water-like fragments (O H H) x 2
+        #[rustfmt::skip]
+        let positions = [
+            0.000, 0.000, 0.000,   1.810, 0.000, 0.000,  -0.450, 1.750, 0.000,
+            5.600, 0.000, 0.000,   6.200, 1.700, 0.000,   6.200,-1.700, 0.000,
+        ];
+        let numbers = [8, 1, 1, 8, 1, 1];
+        let fragments = [0, 0, 0, 1, 1, 1];
+        let structure = DFTD3Structure::new(6, &numbers, &positions, None, None);
+        let model = DFTD3Model::new(&structure);
+        let param = DFTD3Param::load_zero_damping("PBE0", true);
+
+        let (energy, _, _) = get_dispersion(&structure, &model, &param, false, false);
+        let pairwise = PairwiseDispersion::new(&structure, &model, &param);
+        assert!(pairwise.check_energy(energy, 1e-10));
+
+        let partition = pairwise.atom_partition();
+        assert!((partition.iter().sum::<f64>() - energy).abs() < 1e-10);
+
+        let matrix = pairwise.fragment_matrix(&fragments);
+        assert!((matrix[0] + matrix[1] + matrix[3] - energy).abs() < 1e-10);
+        assert_eq!(matrix[1], matrix[2]);
+
+        let contacts = pairwise.strongest_contacts(3);
+        assert_eq!(contacts.len(), 3);
+        assert!(contacts[0].2.abs() >= contacts[1].2.abs());
+        assert!(contacts.iter().all(|&(i, j, _)| i < j));
+
+        assert!(pairwise.fragment_matrix_f(&[0, 1]).is_err());
+    }
+}
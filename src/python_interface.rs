@@ -0,0 +1,353 @@
+//! Python bindings (PyO3) of the Rust wrapper.
+//!
+//! The extension module `rest_dftd3` is built by maturin with feature `python` (see
+//! `pyproject.toml`). Arrays are exchanged as NumPy arrays: positions and gradient of shape
+//! `(natoms, 3)`, lattice and sigma of shape `(3, 3)`, pairwise energies of shape
+//! `(natoms, natoms)`; all quantities are in atomic units. Errors of the library are raised as
+//! `DFTD3Exception`.
+
+use crate::prelude::*;
+use numpy::{PyArray1, PyArray2, PyArrayMethods, PyReadonlyArray2, PyUntypedArrayMethods};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+create_exception!(
+    rest_dftd3,
+    DFTD3Exception,
+    PyException,
+    "Error raised by the DFT-D3 library or its Rust wrapper"
+);
+
+impl From<DFTD3Error> for PyErr {
+    fn from(err: DFTD3Error) -> PyErr {
+        DFTD3Exception::new_err(err.get_message())
+    }
+}
+
+/// Flatten array of given number of columns to row-major vector.
+fn to_row_major(array: &PyReadonlyArray2<f64>, name: &str, ncols: usize) -> PyResult<Vec<f64>> {
+    let shape = array.shape();
+    if shape[1] != ncols {
+        return Err(DFTD3Error::Rust(format!(
+            "Invalid dimension for {}, expected {} columns, got {}",
+            name, ncols, shape[1]
+        ))
+        .into());
+    }
+    Ok(array.as_array().iter().copied().collect())
+}
+
+/// Convert row-major vector to array of shape `(nrows, ncols)`.
+fn to_pyarray<'py>(
+    py: Python<'py>,
+    values: Vec<f64>,
+    ncols: usize,
+) -> PyResult<Bound<'py, PyArray2<f64>>> {
+    let nrows = values.len() / ncols;
+    PyArray1::from_vec(py, values).reshape([nrows, ncols])
+}
+
+#[pyclass(name = "DFTD3Structure", unsendable)]
+pub struct PyDFTD3Structure {
+    inner: DFTD3Structure,
+}
+
+#[pymethods]
+impl PyDFTD3Structure {
+    #[new]
+    #[pyo3(signature = (numbers, positions, lattice=None, periodic=None))]
+    fn new(
+        numbers: Vec<usize>,
+        positions: PyReadonlyArray2<f64>,
+        lattice: Option<PyReadonlyArray2<f64>>,
+        periodic: Option<Vec<bool>>,
+    ) -> PyResult<Self> {
+        let positions = to_row_major(&positions, "positions", 3)?;
+        let lattice = lattice
+            .map(|x| to_row_major(&x, "lattice", 3))
+            .transpose()?;
+        let inner = DFTD3Structure::new_f(
+            numbers.len(),
+            &numbers,
+            &positions,
+            lattice.as_deref(),
+            periodic.as_deref(),
+        )?;
+        Ok(Self { inner })
+    }
+
+    /// Update coordinates and lattice parameters
+    #[pyo3(signature = (positions, lattice=None))]
+    fn update(
+        &mut self,
+        positions: PyReadonlyArray2<f64>,
+        lattice: Option<PyReadonlyArray2<f64>>,
+    ) -> PyResult<()> {
+        let positions = to_row_major(&positions, "positions", 3)?;
+        let lattice = lattice
+            .map(|x| to_row_major(&x, "lattice", 3))
+            .transpose()?;
+        Ok(self.inner.update_f(&positions, lattice.as_deref())?)
+    }
+
+    #[getter]
+    fn natoms(&self) -> usize {
+        self.inner.get_natoms()
+    }
+
+    #[getter]
+    fn numbers(&self) -> Vec<usize> {
+        self.inner.get_numbers().to_vec()
+    }
+
+    #[getter]
+    fn positions<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        to_pyarray(py, self.inner.get_positions(), 3)
+    }
+
+    #[getter]
+    fn lattice<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyArray2<f64>>>> {
+        self.inner
+            .get_lattice()
+            .map(|x| to_pyarray(py, x, 3))
+            .transpose()
+    }
+}
+
+#[pyclass(name = "DFTD3Model", unsendable)]
+pub struct PyDFTD3Model {
+    inner: DFTD3Model,
+}
+
+#[pymethods]
+impl PyDFTD3Model {
+    #[new]
+    fn new(structure: PyRef<'_, PyDFTD3Structure>) -> PyResult<Self> {
+        let inner = DFTD3Model::new_f(&structure.inner)?;
+        Ok(Self { inner })
+    }
+
+    /// Set realspace cutoffs (quantities in Bohr)
+    #[pyo3(signature = (disp2=60.0, disp3=40.0, cn=40.0))]
+    fn set_realspace_cutoff(&self, disp2: f64, disp3: f64, cn: f64) -> PyResult<()> {
+        Ok(self.inner.set_realspace_cutoff_f(disp2, disp3, cn)?)
+    }
+
+    /// Get realspace cutoffs `(disp2, disp3, cn)` (quantities in Bohr)
+    fn get_realspace_cutoff(&self) -> (f64, f64, f64) {
+        let cutoff = self.inner.get_realspace_cutoff();
+        (cutoff.disp2, cutoff.disp3, cutoff.cn)
+    }
+}
+
+#[pyclass(name = "DFTD3Param")]
+pub struct PyDFTD3Param {
+    inner: DFTD3Param,
+}
+
+#[pymethods]
+impl PyDFTD3Param {
+    #[staticmethod]
+    fn new_zero_damping(s6: f64, s8: f64, s9: f64, rs6: f64, rs8: f64, alp: f64) -> PyResult<Self> {
+        let inner = DFTD3Param::new_zero_damping_f(s6, s8, s9, rs6, rs8, alp)?;
+        Ok(Self { inner })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (method, atm=false))]
+    fn load_zero_damping(method: &str, atm: bool) -> PyResult<Self> {
+        let inner = DFTD3Param::load_zero_damping_f(method, atm)?;
+        Ok(Self { inner })
+    }
+
+    #[staticmethod]
+    fn new_rational_damping(
+        s6: f64,
+        s8: f64,
+        s9: f64,
+        a1: f64,
+        a2: f64,
+        alp: f64,
+    ) -> PyResult<Self> {
+        let inner = DFTD3Param::new_rational_damping_f(s6, s8, s9, a1, a2, alp)?;
+        Ok(Self { inner })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (method, atm=false))]
+    fn load_rational_damping(method: &str, atm: bool) -> PyResult<Self> {
+        let inner = DFTD3Param::load_rational_damping_f(method, atm)?;
+        Ok(Self { inner })
+    }
+
+    #[staticmethod]
+    fn new_mzero_damping(
+        s6: f64,
+        s8: f64,
+        s9: f64,
+        rs6: f64,
+        rs8: f64,
+        alp: f64,
+        bet: f64,
+    ) -> PyResult<Self> {
+        let inner = DFTD3Param::new_mzero_damping_f(s6, s8, s9, rs6, rs8, alp, bet)?;
+        Ok(Self { inner })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (method, atm=false))]
+    fn load_mzero_damping(method: &str, atm: bool) -> PyResult<Self> {
+        let inner = DFTD3Param::load_mzero_damping_f(method, atm)?;
+        Ok(Self { inner })
+    }
+
+    #[staticmethod]
+    fn new_mrational_damping(
+        s6: f64,
+        s8: f64,
+        s9: f64,
+        a1: f64,
+        a2: f64,
+        alp: f64,
+    ) -> PyResult<Self> {
+        let inner = DFTD3Param::new_mrational_damping_f(s6, s8, s9, a1, a2, alp)?;
+        Ok(Self { inner })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (method, atm=false))]
+    fn load_mrational_damping(method: &str, atm: bool) -> PyResult<Self> {
+        let inner = DFTD3Param::load_mrational_damping_f(method, atm)?;
+        Ok(Self { inner })
+    }
+
+    #[staticmethod]
+    fn new_optimizedpower_damping(
+        s6: f64,
+        s8: f64,
+        s9: f64,
+        a1: f64,
+        a2: f64,
+        alp: f64,
+        bet: f64,
+    ) -> PyResult<Self> {
+        let inner = DFTD3Param::new_optimizedpower_damping_f(s6, s8, s9, a1, a2, alp, bet)?;
+        Ok(Self { inner })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (method, atm=false))]
+    fn load_optimizedpower_damping(method: &str, atm: bool) -> PyResult<Self> {
+        let inner = DFTD3Param::load_optimizedpower_damping_f(method, atm)?;
+        Ok(Self { inner })
+    }
+}
+
+#[pyclass(name = "DFTD3GCP", unsendable)]
+pub struct PyDFTD3GCP {
+    inner: DFTD3GCP,
+}
+
+#[pymethods]
+impl PyDFTD3GCP {
+    /// Load geometric counter-poise parameters from internal storage
+    #[new]
+    fn new(structure: PyRef<'_, PyDFTD3Structure>, method: &str, basis: &str) -> PyResult<Self> {
+        let inner = DFTD3GCP::load_gcp_param_f(&structure.inner, method, basis)?;
+        Ok(Self { inner })
+    }
+
+    /// Set realspace cutoffs (quantities in Bohr)
+    fn set_realspace_cutoff(&self, bas: f64, srb: f64) -> PyResult<()> {
+        Ok(self.inner.set_realspace_cutoff_f(bas, srb)?)
+    }
+}
+
+type PyDispersionOutput<'py> = (
+    f64,
+    Option<Bound<'py, PyArray2<f64>>>,
+    Option<Bound<'py, PyArray2<f64>>>,
+);
+
+type PyPairwiseOutput<'py> = (Bound<'py, PyArray2<f64>>, Bound<'py, PyArray2<f64>>);
+
+/// Evaluate the dispersion energy and its derivatives
+#[pyfunction]
+#[pyo3(name = "get_dispersion", signature = (structure, model, param, eval_grad=true, eval_sigma=true))]
+fn py_get_dispersion<'py>(
+    py: Python<'py>,
+    structure: PyRef<'_, PyDFTD3Structure>,
+    model: PyRef<'_, PyDFTD3Model>,
+    param: PyRef<'_, PyDFTD3Param>,
+    eval_grad: bool,
+    eval_sigma: bool,
+) -> PyResult<PyDispersionOutput<'py>> {
+    let (energy, grad, sigma) = get_dispersion_f(
+        &structure.inner,
+        &model.inner,
+        &param.inner,
+        eval_grad,
+        eval_sigma,
+    )?;
+    Ok((
+        energy,
+        grad.map(|x| to_pyarray(py, x, 3)).transpose()?,
+        sigma.map(|x| to_pyarray(py, x, 3)).transpose()?,
+    ))
+}
+
+/// Evaluate the pairwise representation of the dispersion energy
+#[pyfunction]
+#[pyo3(name = "get_pairwise_dispersion")]
+fn py_get_pairwise_dispersion<'py>(
+    py: Python<'py>,
+    structure: PyRef<'_, PyDFTD3Structure>,
+    model: PyRef<'_, PyDFTD3Model>,
+    param: PyRef<'_, PyDFTD3Param>,
+) -> PyResult<PyPairwiseOutput<'py>> {
+    let natoms = structure.inner.get_natoms();
+    let (pair_energy2, pair_energy3) =
+        get_pairwise_dispersion_f(&structure.inner, &model.inner, &param.inner)?;
+    Ok((
+        to_pyarray(py, pair_energy2, natoms)?,
+        to_pyarray(py, pair_energy3, natoms)?,
+    ))
+}
+
+/// Evaluate the counterpoise correction
+#[pyfunction]
+#[pyo3(name = "get_counterpoise")]
+fn py_get_counterpoise<'py>(
+    py: Python<'py>,
+    structure: PyRef<'_, PyDFTD3Structure>,
+    gcp: PyRef<'_, PyDFTD3GCP>,
+) -> PyResult<PyDispersionOutput<'py>> {
+    let (energy, grad, sigma) = get_counterpoise_f(&structure.inner, &gcp.inner)?;
+    Ok((
+        energy,
+        Some(to_pyarray(py, grad, 3)?),
+        Some(to_pyarray(py, sigma, 3)?),
+    ))
+}
+
+/// Get the version of the DFTD3 library
+#[pyfunction]
+#[pyo3(name = "get_api_version")]
+fn py_get_api_version() -> String {
+    get_api_version()
+}
+
+#[pymodule]
+fn rest_dftd3(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("DFTD3Exception", m.py().get_type::<DFTD3Exception>())?;
+    m.add_class::<PyDFTD3Structure>()?;
+    m.add_class::<PyDFTD3Model>()?;
+    m.add_class::<PyDFTD3Param>()?;
+    m.add_class::<PyDFTD3GCP>()?;
+    m.add_function(wrap_pyfunction!(py_get_dispersion, m)?)?;
+    m.add_function(wrap_pyfunction!(py_get_pairwise_dispersion, m)?)?;
+    m.add_function(wrap_pyfunction!(py_get_counterpoise, m)?)?;
+    m.add_function(wrap_pyfunction!(py_get_api_version, m)?)?;
+    Ok(())
+}
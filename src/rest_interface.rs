@@ -1,6 +1,249 @@
+//! Entry points for the REST package.
+//!
+//! [`calc_dftd3_rest_f`] is the safe driver; `calc_dftd3_rest_` and `calc_dftd3_atm_rest_` are the
+//! pointer-based entry points called by REST. Quantities are in atomic units. Errors are reported
+//! through `status` of `calc_dftd3_rest_`; the safe `calc_dftd3_rest` and `calc_dftd3_atm_rest_`
+//! panic on failure.
+
 use crate::prelude::*;
 use std::ffi::{c_char, c_double, c_int};
 
+/// Options of REST dispersion calculation
+#[derive(Debug, Clone, PartialEq)]
+pub struct DFTD3RestOptions {
+    /// total charge; not used by D3, which is charge-independent, but kept for charge-dependent
+    /// dispersion models
+    pub charge: f64,
+    /// number of unpaired electrons; not used by D3, kept as `charge`
+    pub uhf: i32,
+    /// include three-body (ATM) dispersion
+    pub atm: bool,
+    /// evaluate gradient
+    pub eval_grad: bool,
+    /// evaluate sigma
+    pub eval_sigma: bool,
+    /// lattice [3][3] of periodic jobs
+    pub lattice: Option<Vec<f64>>,
+    /// periodicity [3] of periodic jobs; all directions periodic if lattice is given without it
+    pub periodic: Option<Vec<bool>>,
+}
+
+impl Default for DFTD3RestOptions {
+    fn default() -> Self {
+        Self {
+            charge: 0.0,
+            uhf: 0,
+            atm: true,
+            eval_grad: true,
+            eval_sigma: true,
+            lattice: None,
+            periodic: None,
+        }
+    }
+}
+
+/// Damping name of [`DFTD3Param::load_damping_f`] from REST correction name (case-insensitive)
+fn rest_damping_name(corr: &str) -> String {
+    let corr = corr.to_lowercase();
+    let name = match corr.as_str() {
+        "d3" | "d3(zero)" => "d3zero",
+        "bj" | "d3(bj)" => "d3bj",
+        "d3(bjm)" => "d3bjm",
+        "d3(zerom)" => "d3zerom",
+        "d3(op)" => "d3op",
+        _ => return corr,
+    };
+    name.to_string()
+}
+
+/// Evaluate dispersion for REST (failable)
+///
+/// # Arguments
+///
+/// * `numbers` - atomic numbers [natoms]
+/// * `positions` - positions [natoms][3] (in Bohr)
+/// * `method` - method of damping parameters, e.g. `b3lyp`
+/// * `corr` - damping kind, e.g. `d3bj`, `d3(bj)`, `zero`, `d3zerom` or `d3op`
+/// * `options` - evaluated quantities, ATM, lattice and periodicity
+#[allow(clippy::type_complexity)]
+pub fn calc_dftd3_rest_f(
+    numbers: &[usize],
+    positions: &[f64],
+    method: &str,
+    corr: &str,
+    options: &DFTD3RestOptions,
+) -> Result<(f64, Option<Vec<f64>>, Option<Vec<f64>>), DFTD3Error> {
+    let periodic = match (&options.lattice, &options.periodic) {
+        (Some(_), None) => Some(vec![true; 3]),
+        (_, periodic) => periodic.clone(),
+    };
+    let structure = DFTD3Structure::new_f(
+        numbers.len(),
+        numbers,
+        positions,
+        options.lattice.as_deref(),
+        periodic.as_deref(),
+    )?;
+    let model = DFTD3Model::new_f(&structure)?;
+    let param = DFTD3Param::load_damping_f(&rest_damping_name(corr), method, options.atm)?;
+    get_dispersion_f(
+        &structure,
+        &model,
+        &param,
+        options.eval_grad,
+        options.eval_sigma,
+    )
+}
+
+/// Evaluate dispersion for REST
+pub fn calc_dftd3_rest(
+    numbers: &[usize],
+    positions: &[f64],
+    method: &str,
+    corr: &str,
+    options: &DFTD3RestOptions,
+) -> (f64, Option<Vec<f64>>, Option<Vec<f64>>) {
+    calc_dftd3_rest_f(numbers, positions, method, corr, options).unwrap()
+}
+
+/// String of given length from character pointer; trailing blanks (Fortran padding) are removed
+pub(crate) unsafe fn read_fixed_string(ptr: *const c_char, len: *const c_int) -> String {
+    let chars = unsafe { std::slice::from_raw_parts(ptr, *len as usize) };
+    let chars = chars.iter().map(|&x| x as u8).collect::<Vec<u8>>();
+    String::from_utf8_lossy(&chars).trim_end().to_string()
+}
+
+/// Value of optional flag pointer; `default` if null
+unsafe fn read_flag(flag: *const c_int, default: bool) -> bool {
+    match flag.is_null() {
+        true => default,
+        false => unsafe { *flag != 0 },
+    }
+}
+
+/// Report result of pointer-based entry point through `status` (0 on success, 1 on failure) and
+/// space-padded `errmsg`; panics on failure if `status` is null.
+unsafe fn report_status(
+    result: Result<(), DFTD3Error>,
+    status: *mut c_int,
+    errmsg: *mut c_char,
+    errmsg_len: *const c_int,
+) {
+    match (result, status.is_null()) {
+        (Ok(()), true) => (),
+        (Ok(()), false) => unsafe { *status = 0 },
+        (Err(err), true) => panic!("{}", err.get_message()),
+        (Err(err), false) => unsafe {
+            *status = 1;
+            if !errmsg.is_null() && !errmsg_len.is_null() {
+                let buffer = std::slice::from_raw_parts_mut(
+                    errmsg as *mut u8,
+                    (*errmsg_len).max(0) as usize,
+                );
+                let message = err.get_message();
+                let message = message.as_bytes();
+                let n = message.len().min(buffer.len());
+                buffer.fill(b' ');
+                buffer[..n].copy_from_slice(&message[..n]);
+            }
+        },
+    }
+}
+
+/// Evaluate dispersion for REST with optional lattice, periodicity, flags and outputs
+///
+/// Null pointers are allowed for `lattice` (molecular job), `periodic` (all directions periodic
+/// if lattice is given), `atm` (ATM included), `eval_grad` and `eval_sigma`, and for `gradient`
+/// and `sigma`. Without flags, gradient and sigma are evaluated if their output pointers are not
+/// null. `charge` and `uhf` may be null; they are routed into [`DFTD3RestOptions`] but not used by
+/// D3.
+///
+/// On success `status` is 0. On failure (e.g. unknown method or damping, or singular lattice) it
+/// is 1, outputs are left untouched, and the message is written space-padded to `errmsg` if given.
+///
+/// # Panics
+///
+/// Panics on failure if `status` is null.
+///
+/// # Safety
+///
+/// All non-null pointers must be valid for the dimensions given by `num_size` (`num`: natoms,
+/// `xyz` and `gradient`: 3 * natoms, `lattice` and `sigma`: 9, `periodic`: 3) and by `method_len`,
+/// `corr_len` and `errmsg_len`.
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn calc_dftd3_rest_(
+    num: *const c_int,
+    num_size: *const c_int,
+    xyz: *const c_double,
+    lattice: *const c_double,
+    periodic: *const c_int,
+    charge: *const c_double,
+    uhf: *const c_int,
+    method: *const c_char,
+    method_len: *const c_int,
+    corr: *const c_char,
+    corr_len: *const c_int,
+    atm: *const c_int,
+    eval_grad: *const c_int,
+    eval_sigma: *const c_int,
+    energy: *mut c_double,
+    gradient: *mut c_double,
+    sigma: *mut c_double,
+    status: *mut c_int,
+    errmsg: *mut c_char,
+    errmsg_len: *const c_int,
+) {
+    // convert c-style arguments to rust-style arguments
+    let natoms = unsafe { *num_size }.max(0) as usize;
+    let numbers = unsafe { std::slice::from_raw_parts(num, natoms) };
+    let numbers = numbers.iter().map(|&x| x as usize).collect::<Vec<usize>>();
+    let positions = unsafe { std::slice::from_raw_parts(xyz, natoms * 3) };
+    let method = unsafe { read_fixed_string(method, method_len) };
+    let corr = unsafe { read_fixed_string(corr, corr_len) };
+    let options = unsafe {
+        DFTD3RestOptions {
+            charge: if charge.is_null() { 0.0 } else { *charge },
+            uhf: if uhf.is_null() { 0 } else { *uhf },
+            atm: read_flag(atm, true),
+            eval_grad: read_flag(eval_grad, !gradient.is_null()),
+            eval_sigma: read_flag(eval_sigma, !sigma.is_null()),
+            lattice: (!lattice.is_null()).then(|| std::slice::from_raw_parts(lattice, 9).to_vec()),
+            periodic: (!periodic.is_null()).then(|| {
+                let periodic = std::slice::from_raw_parts(periodic, 3);
+                periodic.iter().map(|&x| x != 0).collect()
+            }),
+        }
+    };
+
+    // set energy, gradient and sigma
+    let result = calc_dftd3_rest_f(&numbers, positions, &method, &corr, &options).map(
+        |(result_energy, result_gradient, result_sigma)| unsafe {
+            *energy = result_energy;
+            if let (Some(result), false) = (result_gradient, gradient.is_null()) {
+                std::slice::from_raw_parts_mut(gradient, natoms * 3).copy_from_slice(&result);
+            }
+            if let (Some(result), false) = (result_sigma, sigma.is_null()) {
+                std::slice::from_raw_parts_mut(sigma, 9).copy_from_slice(&result);
+            }
+        },
+    );
+    unsafe { report_status(result, status, errmsg, errmsg_len) }
+}
+
+/// Evaluate dispersion with ATM for molecular REST jobs
+///
+/// Gradient and sigma are evaluated unless their pointers are null. `charge` and `uhf` are not
+/// used by D3. See also `calc_dftd3_rest_`.
+///
+/// # Panics
+///
+/// Panics on failure, e.g. for unknown method or damping; use `calc_dftd3_rest_` with `status` to
+/// handle errors.
+///
+/// # Safety
+///
+/// See `calc_dftd3_rest_`.
+#[allow(clippy::too_many_arguments)]
 pub unsafe fn calc_dftd3_atm_rest_(
     num: *const c_int,
     num_size: *const c_int,
@@ -15,53 +258,358 @@ pub unsafe fn calc_dftd3_atm_rest_(
     corr: *const c_char,
     corr_len: *const c_int,
 ) {
-    let _ = charge;
-    let _ = uhf;
+    let null_int = std::ptr::null();
+    unsafe {
+        calc_dftd3_rest_(
+            num,
+            num_size,
+            xyz,
+            std::ptr::null(),
+            null_int,
+            charge,
+            uhf,
+            method,
+            method_len,
+            corr,
+            corr_len,
+            null_int,
+            null_int,
+            null_int,
+            energy,
+            gradient,
+            sigma,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null(),
+        )
+    }
+}
 
-    // convert c-style arguments to rust-style arguments
-    let natoms = unsafe { *num_size } as usize;
-    let charges = {
-        let charges = unsafe { std::slice::from_raw_parts(num, natoms) };
-        charges.iter().map(|&x| x as usize).collect::<Vec<usize>>()
-    };
-    let coords = {
-        let coords = unsafe { std::slice::from_raw_parts(xyz, natoms * 3) };
-        coords.iter().map(|&x| x).collect::<Vec<f64>>() // this may not required, since c_double is always f64
-    };
-    let method = {
-        let method = unsafe { std::slice::from_raw_parts(method, *method_len as usize) };
-        let method = method.iter().map(|&x| x as u8).collect::<Vec<u8>>();
-        std::str::from_utf8(&method).unwrap().to_string()
-    };
-    let corr = {
-        let corr = unsafe { std::slice::from_raw_parts(corr, *corr_len as usize) };
-        let corr = corr.iter().map(|&x| x as u8).collect::<Vec<u8>>();
-        std::str::from_utf8(&corr).unwrap().to_string()
+/// Atomic numbers from space-padded element labels of fixed length (failable)
+///
+/// Labels are matched case-insensitively by their leading letters, so that labels such as `C1` or
+/// `h_a` are accepted.
+pub fn numbers_from_symbols_f(symbols: &[u8], symbol_len: usize) -> Result<Vec<usize>, DFTD3Error> {
+    if symbol_len == 0 || symbols.len() % symbol_len != 0 {
+        return Err(DFTD3Error::Rust(format!(
+            "Invalid dimension for symbols, expected multiple of {}, got {}",
+            symbol_len,
+            symbols.len()
+        )));
+    }
+    symbols
+        .chunks(symbol_len)
+        .map(|label| {
+            let label = String::from_utf8_lossy(label);
+            let symbol = label
+                .trim()
+                .chars()
+                .take_while(|c| c.is_ascii_alphabetic())
+                .collect::<String>();
+            element_number(&symbol).ok_or_else(|| {
+                DFTD3Error::Rust(format!("Unknown element symbol {:?}", label.trim()))
+            })
+        })
+        .collect()
+}
+
+/// Evaluate dispersion for REST from element symbols (failable)
+///
+/// # Arguments
+///
+/// * `symbols` - space-padded element labels, `symbol_len` characters each
+/// * `positions` - positions [natoms][3] in `units.length`
+/// * `options` - as in [`calc_dftd3_rest_f`], lattice in `units.length`
+///
+/// Energy and sigma are returned in `units.energy`, gradient in energy per length unit.
+#[allow(clippy::type_complexity)]
+pub fn calc_dftd3_rest_symbols_f(
+    symbols: &[u8],
+    symbol_len: usize,
+    positions: &[f64],
+    units: Units,
+    method: &str,
+    corr: &str,
+    options: &DFTD3RestOptions,
+) -> Result<(f64, Option<Vec<f64>>, Option<Vec<f64>>), DFTD3Error> {
+    let numbers = numbers_from_symbols_f(symbols, symbol_len)?;
+    let options = DFTD3RestOptions {
+        lattice: options.lattice.as_ref().map(|x| units.length_to_bohr(x)),
+        ..options.clone()
     };
+    let positions = units.length_to_bohr(positions);
+    let (energy, gradient, sigma) =
+        calc_dftd3_rest_f(&numbers, &positions, method, corr, &options)?;
+    Ok((
+        units.energy_from_hartree(energy),
+        gradient.map(|x| units.gradient_from_atomic(&x)),
+        sigma.map(|x| x.iter().map(|&x| units.energy_from_hartree(x)).collect()),
+    ))
+}
 
-    // create structure and model
-    let structure = DFTD3Structure::new(natoms, &charges, &coords, None, None);
-    let model = DFTD3Model::new(&structure);
+/// Evaluate dispersion for REST from Fortran element symbols, with error status
+///
+/// `symbols` holds `num_size` space-padded labels of `symbol_len` characters (a Fortran
+/// `character(len=symbol_len) :: symbols(natoms)` array). `unit` selects the length unit of `xyz`
+/// and `lattice`: 0 (or null) for Bohr, 1 for Angstrom. Energy and sigma are returned in Hartree,
+/// gradient in Hartree per length unit.
+///
+/// Null pointers are allowed for `unit`, `lattice`, `periodic`, `atm`, `gradient` and `sigma`, as
+/// in `calc_dftd3_rest_`, and for `errmsg`. On success `status` is 0. On failure it is 1, outputs
+/// are left untouched, and the message is written space-padded to `errmsg` if given.
+///
+/// The symbol is exported unmangled with C ABI. All arguments are passed by reference, including
+/// the character lengths, so Fortran must declare it through `bind(C)` rather than rely on hidden
+/// length arguments:
+///
+/// ```fortran
+/// interface
+///     subroutine calc_dftd3_rest_symbols(symbols, symbol_len, num_size, xyz, unit, lattice, &
+///             periodic, method, method_len, corr, corr_len, atm, energy, gradient, sigma, &
+///             status, errmsg, errmsg_len) bind(C, name="calc_dftd3_rest_symbols_")
+///         use iso_c_binding
+///         character(kind=c_char), intent(in) :: symbols(*), method(*), corr(*)
+///         integer(c_int), intent(in) :: symbol_len, num_size, method_len, corr_len, errmsg_len
+///         real(c_double), intent(in) :: xyz(3, *)
+///         integer(c_int), intent(in), optional :: unit, periodic(3), atm
+///         real(c_double), intent(in), optional :: lattice(3, 3)
+///         real(c_double), intent(out) :: energy
+///         real(c_double), intent(out), optional :: gradient(3, *), sigma(3, 3)
+///         integer(c_int), intent(out) :: status
+///         character(kind=c_char), intent(out), optional :: errmsg(*)
+///     end subroutine
+/// end interface
+/// ```
+///
+/// Absent `optional` arguments are passed as null pointers (Fortran 2018). A
+/// `character(len=n) :: symbols(natoms)` array is passed to `symbols(*)` by sequence association.
+///
+/// # Safety
+///
+/// `symbols` must be valid for `num_size * symbol_len` characters, `errmsg` for `errmsg_len`
+/// characters; see `calc_dftd3_rest_` for other pointers.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn calc_dftd3_rest_symbols_(
+    symbols: *const c_char,
+    symbol_len: *const c_int,
+    num_size: *const c_int,
+    xyz: *const c_double,
+    unit: *const c_int,
+    lattice: *const c_double,
+    periodic: *const c_int,
+    method: *const c_char,
+    method_len: *const c_int,
+    corr: *const c_char,
+    corr_len: *const c_int,
+    atm: *const c_int,
+    energy: *mut c_double,
+    gradient: *mut c_double,
+    sigma: *mut c_double,
+    status: *mut c_int,
+    errmsg: *mut c_char,
+    errmsg_len: *const c_int,
+) {
+    let natoms = unsafe { *num_size }.max(0) as usize;
+    let symbol_len = unsafe { *symbol_len }.max(0) as usize;
+    let result = (|| {
+        let unit = if unit.is_null() { 0 } else { unsafe { *unit } };
+        let units = match unit {
+            0 => Units::ATOMIC,
+            1 => Units::new(LengthUnit::Angstrom, EnergyUnit::Hartree),
+            unit => {
+                return Err(DFTD3Error::Rust(format!(
+                    "Invalid unit flag, expected 0 (Bohr) or 1 (Angstrom), got {}",
+                    unit
+                )))
+            }
+        };
+        let symbols =
+            unsafe { std::slice::from_raw_parts(symbols as *const u8, natoms * symbol_len) };
+        let positions = unsafe { std::slice::from_raw_parts(xyz, natoms * 3) };
+        let method = unsafe { read_fixed_string(method, method_len) };
+        let corr = unsafe { read_fixed_string(corr, corr_len) };
+        let options = unsafe {
+            DFTD3RestOptions {
+                atm: read_flag(atm, true),
+                eval_grad: !gradient.is_null(),
+                eval_sigma: !sigma.is_null(),
+                lattice: (!lattice.is_null())
+                    .then(|| std::slice::from_raw_parts(lattice, 9).to_vec()),
+                periodic: (!periodic.is_null()).then(|| {
+                    let periodic = std::slice::from_raw_parts(periodic, 3);
+                    periodic.iter().map(|&x| x != 0).collect()
+                }),
+                ..Default::default()
+            }
+        };
+        calc_dftd3_rest_symbols_f(
+            symbols, symbol_len, positions, units, &method, &corr, &options,
+        )
+    })();
 
-    // get dispersion energy and gradient
-    let result = match corr.as_str() {
-        "zero" | "d3zero" | "d3(zero)" | "d3" => {
-            let param = DFTD3Param::load_zero_damping(&method, true);
-            get_dispersion(&structure, &model, &param, true, true)
+    let result = result.map(|(result_energy, result_gradient, result_sigma)| unsafe {
+        *energy = result_energy;
+        if let Some(result) = result_gradient {
+            std::slice::from_raw_parts_mut(gradient, natoms * 3).copy_from_slice(&result);
         }
-        "bj" | "d3bj" | "d3(bj)" | "rational" => {
-            let param = DFTD3Param::load_rational_damping(&method, true);
-            get_dispersion(&structure, &model, &param, true, true)
+        if let Some(result) = result_sigma {
+            std::slice::from_raw_parts_mut(sigma, 9).copy_from_slice(&result);
         }
-        _ => panic!("Unknown damping type"),
-    };
+    });
+    unsafe { report_status(result, status, errmsg, errmsg_len) }
+}
 
-    // set energy and gradient
-    unsafe {
-        *energy = result.0;
-        let gradient = std::slice::from_raw_parts_mut(gradient, natoms * 3);
-        let sigma = std::slice::from_raw_parts_mut(sigma, 3 * 3);
-        gradient.copy_from_slice(&result.1.unwrap());
-        sigma.copy_from_slice(&result.2.unwrap());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rest_interface() {
+        let numbers = [8, 1, 1];
+        let positions = [0.0, 0.0, 0.0, 1.81, 0.0, 0.0, -0.45, 1.75, 0.0];
+        let structure = DFTD3Structure::new(3, &numbers, &positions, None, None);
+        let model = DFTD3Model::new(&structure);
+        let param = DFTD3Param::load_rational_damping("b3lyp", true);
+        let (reference, grad_ref, _) = get_dispersion(&structure, &model, &param, true, false);
+
+        // energy only, null gradient and sigma
+        let num = [8, 1, 1];
+        let (method, corr) = ("b3lyp   ", "D3(BJ)");
+        let mut energy = 0.0;
+        unsafe {
+            calc_dftd3_atm_rest_(
+                num.as_ptr(),
+                &3,
+                positions.as_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+                method.as_ptr() as *const c_char,
+                &(method.len() as c_int),
+                &mut energy,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                corr.as_ptr() as *const c_char,
+                &(corr.len() as c_int),
+            )
+        };
+        assert!((energy - reference).abs() < 1e-12);
+
+        // errors are reported through status and message
+        let (corr, mut status) = ("d3xx", -1);
+        let mut errmsg = [0 as c_char; 64];
+        unsafe {
+            calc_dftd3_rest_(
+                num.as_ptr(),
+                &3,
+                positions.as_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null(),
+                method.as_ptr() as *const c_char,
+                &(method.len() as c_int),
+                corr.as_ptr() as *const c_char,
+                &(corr.len() as c_int),
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null(),
+                &mut energy,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                &mut status,
+                errmsg.as_mut_ptr(),
+                &64,
+            )
+        };
+        assert_eq!(status, 1);
+        let message = errmsg.iter().map(|&c| c as u8 as char).collect::<String>();
+        assert!(message.trim_end().contains("Unknown damping kind"));
+
+        // safe driver with flags
+        let options = DFTD3RestOptions {
+            eval_sigma: false,
+            ..Default::default()
+        };
+        let (energy, gradient, sigma) =
+            calc_dftd3_rest(&numbers, &positions, "b3lyp", "bj", &options);
+        assert!((energy - reference).abs() < 1e-12);
+        assert_eq!(gradient, grad_ref);
+        assert!(sigma.is_none());
+
+        // periodic job
+        let lattice = vec![12.0, 0.0, 0.0, 0.0, 12.0, 0.0, 0.0, 0.0, 12.0];
+        let options = DFTD3RestOptions {
+            lattice: Some(lattice.clone()),
+            ..Default::default()
+        };
+        let (energy, _, sigma) = calc_dftd3_rest(&numbers, &positions, "b3lyp", "d3bj", &options);
+        let structure = DFTD3Structure::new(3, &numbers, &positions, Some(&lattice), None);
+        let model = DFTD3Model::new(&structure);
+        let (reference, _, sigma_ref) = get_dispersion(&structure, &model, &param, false, true);
+        assert!((energy - reference).abs() < 1e-12);
+        assert_eq!(sigma, sigma_ref);
+    }
+
+    #[test]
+    fn test_rest_symbols() {
+        let positions = [0.0, 0.0, 0.0, 1.81, 0.0, 0.0, -0.45, 1.75, 0.0];
+        let positions_angstrom = positions.map(|x| x * BOHR_TO_ANGSTROM);
+        let structure = DFTD3Structure::new(3, &[8, 1, 1], &positions, None, None);
+        let model = DFTD3Model::new(&structure);
+        let param = DFTD3Param::load_rational_damping("b3lyp", true);
+        let (reference, grad_ref, _) = get_dispersion(&structure, &model, &param, true, false);
+
+        let symbols = b"O   h1  H2  ";
+        let (method, corr) = ("b3lyp", "d3bj    ");
+        let call = |symbols: &[u8], xyz: &[f64], unit: Option<c_int>| {
+            let (mut energy, mut gradient, mut status) = (0.0, [0.0; 9], -1);
+            let mut errmsg = [0 as c_char; 64];
+            unsafe {
+                calc_dftd3_rest_symbols_(
+                    symbols.as_ptr() as *const c_char,
+                    &4,
+                    &3,
+                    xyz.as_ptr(),
+                    unit.as_ref().map_or(std::ptr::null(), |unit| unit),
+                    std::ptr::null(),
+                    std::ptr::null(),
+                    method.as_ptr() as *const c_char,
+                    &(method.len() as c_int),
+                    corr.as_ptr() as *const c_char,
+                    &(corr.len() as c_int),
+                    std::ptr::null(),
+                    &mut energy,
+                    gradient.as_mut_ptr(),
+                    std::ptr::null_mut(),
+                    &mut status,
+                    errmsg.as_mut_ptr(),
+                    &64,
+                )
+            };
+            let message = errmsg.iter().map(|&c| c as u8 as char).collect::<String>();
+            (status, energy, gradient, message)
+        };
+
+        // Angstrom input, gradient in Hartree/Angstrom
+        let (status, energy, gradient, _) = call(symbols, &positions_angstrom, Some(1));
+        assert_eq!(status, 0);
+        assert!((energy - reference).abs() < 1e-12);
+        for (g, g_ref) in gradient.iter().zip(&grad_ref.unwrap()) {
+            assert!((g - g_ref / BOHR_TO_ANGSTROM).abs() < 1e-10);
+        }
+
+        // null unit for Bohr input
+        let (status, energy, _, _) = call(symbols, &positions, None);
+        assert_eq!(status, 0);
+        assert!((energy - reference).abs() < 1e-12);
+
+        // errors are reported through status and message
+        let (status, _, _, message) = call(b"O   Xx  H   ", &positions_angstrom, Some(1));
+        assert_eq!(status, 1);
+        assert!(message.trim_end().contains("Unknown element symbol"));
+        let (status, _, _, message) = call(symbols, &positions_angstrom, Some(2));
+        assert_eq!(status, 1);
+        assert!(message.contains("Invalid unit flag"));
     }
 }
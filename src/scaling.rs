@@ -0,0 +1,229 @@
+//! Per-pair scaling of dispersion contributions.
+//!
+//! The scaled dispersion energy is `E = sum_ij s_ij E_ij`, where `E_ij` are the (two-body and
+//! three-body) pairwise energies from [`get_pairwise_dispersion`]. This allows to switch off or
+//! scale dispersion between regions, e.g. intra-QM off, QM-MM on and MM-MM off in QM/MM or
+//! ONIOM-type schemes.
+//!
+//! Gradients of the scaled energy are not supported: pairwise energies depend on all atomic
+//! positions through coordination numbers and three-body terms, and the C API does not expose their
+//! derivatives. By default ([`ScaledGradient::None`]) only the energy is evaluated. A numerical
+//! gradient by central differences of scaled pairwise energies can be requested explicitly with
+//! [`ScaledGradient::FiniteDifference`]; this costs `6 * natoms` pairwise evaluations, i.e.
+//! `O(natoms^3)` work for two-body terms, and is not usable for QM/MM-sized systems. Note that this
+//! is the gradient of the scaled pairwise sum, not that of a subtractive ONIOM energy.
+
+use crate::prelude::*;
+
+/// Displacement step of atomic positions (in Bohr) for gradient of scaled dispersion
+pub const SCALED_GRADIENT_STEP: f64 = 1.0e-4;
+
+/// Evaluation of gradient of scaled dispersion
+///
+/// Analytic gradients are not supported; the default is [`ScaledGradient::None`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ScaledGradient {
+    /// energy only (default)
+    #[default]
+    None,
+    /// central differences with displacement step (in Bohr); the error is of order `step^2`
+    /// (about 1e-8 Hartree/Bohr for [`SCALED_GRADIENT_STEP`]). This costs `6 * natoms` pairwise
+    /// evaluations (`O(natoms^3)` in total), and is only practical for small systems.
+    FiniteDifference { step: f64 },
+}
+
+/// Scaling factors of pairwise dispersion contributions
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairScaling {
+    natoms: usize,
+    /// scaling factor of each atom pair [natoms][natoms]
+    scaling: Vec<f64>,
+}
+
+impl PairScaling {
+    /// Create pair scaling from scaling matrix [natoms][natoms] (failable)
+    pub fn from_matrix_f(natoms: usize, scaling: Vec<f64>) -> Result<Self, DFTD3Error> {
+        if scaling.len() != natoms * natoms {
+            return Err(DFTD3Error::Rust(format!(
+                "Invalid dimension for scaling, expected {}, got {}",
+                natoms * natoms,
+                scaling.len()
+            )));
+        }
+        Ok(Self { natoms, scaling })
+    }
+
+    /// Create pair scaling from scaling matrix [natoms][natoms]
+    pub fn from_matrix(natoms: usize, scaling: Vec<f64>) -> Self {
+        Self::from_matrix_f(natoms, scaling).unwrap()
+    }
+
+    /// Create pair scaling from region labels and region scaling matrix (failable)
+    ///
+    /// # Arguments
+    ///
+    /// * `regions` - region index of each atom [natoms]; number of regions is the maximum index
+    ///   plus one. Labels may also be derived from elements for per-element scaling.
+    /// * `region_scaling` - scaling factor between regions [nreg][nreg]
+    pub fn from_regions_f(regions: &[usize], region_scaling: &[f64]) -> Result<Self, DFTD3Error> {
+        let nreg = regions.iter().max().map_or(0, |&x| x + 1);
+        if region_scaling.len() != nreg * nreg {
+            return Err(DFTD3Error::Rust(format!(
+                "Invalid dimension for region_scaling, expected {}, got {}",
+                nreg * nreg,
+                region_scaling.len()
+            )));
+        }
+        let scaling = regions
+            .iter()
+            .flat_map(|&a| regions.iter().map(move |&b| region_scaling[a * nreg + b]))
+            .collect();
+        Ok(Self {
+            natoms: regions.len(),
+            scaling,
+        })
+    }
+
+    /// Create pair scaling from region labels and region scaling matrix
+    pub fn from_regions(regions: &[usize], region_scaling: &[f64]) -> Self {
+        Self::from_regions_f(regions, region_scaling).unwrap()
+    }
+
+    /// Get number of atoms
+    pub fn get_natoms(&self) -> usize {
+        self.natoms
+    }
+
+    /// Get scaling matrix [natoms][natoms]
+    pub fn get_scaling(&self) -> &[f64] {
+        &self.scaling
+    }
+
+    /// Scaled sum of pairwise energies
+    pub fn scaled_energy(&self, pairwise: &PairwiseDispersion) -> f64 {
+        let n = self.natoms;
+        (0..n)
+            .flat_map(|i| (0..n).map(move |j| (i, j)))
+            .map(|(i, j)| self.scaling[i * n + j] * pairwise[(i, j)])
+            .sum()
+    }
+}
+
+/// Evaluate the scaled dispersion energy and its gradient (failable)
+///
+/// Gradient is evaluated as given by `gradient` (see [`ScaledGradient`] for cost and accuracy).
+/// Displacements are performed by [`DFTD3Structure::update_f`]; the original positions are restored
+/// afterwards.
+pub fn get_scaled_dispersion_f(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    scaling: &PairScaling,
+    gradient: ScaledGradient,
+) -> Result<(f64, Option<Vec<f64>>), DFTD3Error> {
+    if scaling.get_natoms() != structure.get_natoms() {
+        return Err(DFTD3Error::Rust(format!(
+            "Invalid dimension for scaling, expected {}, got {}",
+            structure.get_natoms(),
+            scaling.get_natoms()
+        )));
+    }
+    let energy = scaling.scaled_energy(&PairwiseDispersion::new_f(structure, model, param)?);
+    let h = match gradient {
+        ScaledGradient::None => return Ok((energy, None)),
+        ScaledGradient::FiniteDifference { step } if step.is_nan() || step <= 0.0 => {
+            return Err(DFTD3Error::Rust(format!(
+                "Invalid finite-difference step {}, expected positive value",
+                step
+            )));
+        }
+        ScaledGradient::FiniteDifference { step } => step,
+    };
+
+    let positions = structure.get_positions();
+    let energy_at = |positions: &[f64]| {
+        structure.update_f(positions, None)?;
+        let pairwise = PairwiseDispersion::new_f(structure, model, param)?;
+        Ok(scaling.scaled_energy(&pairwise))
+    };
+    let gradient = (0..positions.len())
+        .map(|i| {
+            let mut displaced = positions.clone();
+            displaced[i] = positions[i] + h;
+            let energy_p = energy_at(&displaced)?;
+            displaced[i] = positions[i] - h;
+            let energy_m = energy_at(&displaced)?;
+            Ok((energy_p - energy_m) / (2.0 * h))
+        })
+        .collect::<Result<Vec<f64>, DFTD3Error>>();
+    structure.update_f(&positions, None)?;
+    Ok((energy, Some(gradient?)))
+}
+
+/// Evaluate the scaled dispersion energy and its gradient
+///
+/// See also [`get_scaled_dispersion_f`].
+pub fn get_scaled_dispersion(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    scaling: &PairScaling,
+    gradient: ScaledGradient,
+) -> (f64, Option<Vec<f64>>) {
+    get_scaled_dispersion_f(structure, model, param, scaling, gradient).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_scaling() {
+        // water dimer (O H H) x 2, first molecule as QM region
+        #[rustfmt::skip]
+        let positions = [
+            0.000, 0.000, 0.000,   1.810, 0.000, 0.000,  -0.450, 1.750, 0.000,
+            5.600, 0.000, 0.000,   6.200, 1.700, 0.000,   6.200,-1.700, 0.000,
+        ];
+        let numbers = [8, 1, 1, 8, 1, 1];
+        let regions = [0, 0, 0, 1, 1, 1];
+        let structure = DFTD3Structure::new(6, &numbers, &positions, None, None);
+        let model = DFTD3Model::new(&structure);
+        let param = DFTD3Param::load_rational_damping("B3LYP", false);
+
+        // unit scaling reproduces dispersion energy and gradient
+        let scaling = PairScaling::from_regions(&regions, &[1.0; 4]);
+        let (energy, gradient) = get_scaled_dispersion(
+            &structure,
+            &model,
+            &param,
+            &scaling,
+            ScaledGradient::FiniteDifference {
+                step: SCALED_GRADIENT_STEP,
+            },
+        );
+        let (reference, grad_ref, _) = get_dispersion(&structure, &model, &param, true, false);
+        assert!((energy - reference).abs() < 1e-12);
+        let diff = gradient
+            .unwrap()
+            .into_iter()
+            .zip(grad_ref.unwrap())
+            .map(|(a, b)| (a - b).abs());
+        assert!(diff.fold(0.0, f64::max) < 1e-8);
+        assert_eq!(structure.get_positions(), positions);
+
+        // QM-MM only equals off-diagonal elements of fragment matrix
+        let scaling = PairScaling::from_regions(&regions, &[0.0, 1.0, 1.0, 0.0]);
+        let (energy, gradient) = get_scaled_dispersion(
+            &structure,
+            &model,
+            &param,
+            &scaling,
+            ScaledGradient::default(),
+        );
+        assert!(gradient.is_none());
+        let pairwise = PairwiseDispersion::new(&structure, &model, &param);
+        let matrix = pairwise.fragment_matrix(&regions);
+        assert!((energy - matrix[1]).abs() < 1e-12);
+    }
+}
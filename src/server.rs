@@ -0,0 +1,247 @@
+//! i-PI socket client driver.
+//!
+//! i-PI and the socket calculator of ASE act as servers of the i-PI protocol and send positions
+//! and cell of each step to a client driver, which returns energy, forces and virial. This module
+//! implements such a driver over a UNIX or TCP socket, keeping one cached [`DFTD3Structure`] that
+//! is updated by [`DFTD3Structure::update_f`] at each step.
+//!
+//! All quantities of the protocol are in atomic units. The cell is received with lattice vectors
+//! as columns, and the virial is returned as `-sigma`.
+
+use crate::prelude::*;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+/// Length of message headers of the i-PI protocol
+pub const IPI_HEADER_LEN: usize = 12;
+
+/// Address of i-PI server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IpiAddress {
+    /// UNIX socket; a name without `/` refers to `/tmp/ipi_<name>` as in i-PI
+    Unix(String),
+    /// TCP socket as `host:port`
+    Tcp(String),
+}
+
+impl IpiAddress {
+    /// Path of UNIX socket, or `host:port` of TCP socket
+    pub fn get_path(&self) -> String {
+        match self {
+            IpiAddress::Unix(name) if !name.contains('/') => format!("/tmp/ipi_{}", name),
+            IpiAddress::Unix(path) => path.clone(),
+            IpiAddress::Tcp(address) => address.clone(),
+        }
+    }
+}
+
+fn io_error(err: std::io::Error) -> DFTD3Error {
+    DFTD3Error::Rust(format!("i-PI socket error: {}", err))
+}
+
+fn send_header(stream: &mut impl Write, header: &str) -> std::io::Result<()> {
+    let mut buffer = [b' '; IPI_HEADER_LEN];
+    buffer[..header.len()].copy_from_slice(header.as_bytes());
+    stream.write_all(&buffer)
+}
+
+fn recv_header(stream: &mut impl Read) -> std::io::Result<String> {
+    let mut buffer = [0u8; IPI_HEADER_LEN];
+    stream.read_exact(&mut buffer)?;
+    Ok(String::from_utf8_lossy(&buffer).trim().to_string())
+}
+
+fn recv_i32(stream: &mut impl Read) -> std::io::Result<i32> {
+    let mut buffer = [0u8; 4];
+    stream.read_exact(&mut buffer)?;
+    Ok(i32::from_ne_bytes(buffer))
+}
+
+fn recv_f64s(stream: &mut impl Read, n: usize) -> std::io::Result<Vec<f64>> {
+    let mut buffer = vec![0u8; 8 * n];
+    stream.read_exact(&mut buffer)?;
+    Ok(buffer
+        .chunks_exact(8)
+        .map(|x| f64::from_ne_bytes(x.try_into().unwrap()))
+        .collect())
+}
+
+fn send_f64s(stream: &mut impl Write, values: &[f64]) -> std::io::Result<()> {
+    let buffer = values
+        .iter()
+        .flat_map(|x| x.to_ne_bytes())
+        .collect::<Vec<u8>>();
+    stream.write_all(&buffer)
+}
+
+/// Transpose of 3x3 matrix (row-major)
+fn transpose(m: &[f64]) -> Vec<f64> {
+    (0..9).map(|ij| m[3 * (ij % 3) + ij / 3]).collect()
+}
+
+/// Result of one step of i-PI driver
+#[derive(Debug, Clone, PartialEq)]
+pub struct IpiResult {
+    /// dispersion energy
+    pub energy: f64,
+    /// forces [natoms][3]
+    pub forces: Vec<f64>,
+    /// virial [3][3], i.e. `-sigma`
+    pub virial: Vec<f64>,
+}
+
+/// i-PI client driver evaluating dispersion
+pub struct IpiDriver {
+    numbers: Vec<usize>,
+    param: DFTD3Param,
+    periodic: bool,
+    cutoff: RealspaceCutoff,
+    // cached structure and model, created at the first step
+    state: Option<(DFTD3Structure, DFTD3Model)>,
+}
+
+impl IpiDriver {
+    /// Create driver for atoms of given atomic numbers
+    ///
+    /// The i-PI protocol does not transfer elements, so atomic numbers must be given in the order
+    /// of atoms of the server. The cell received from server is used as lattice only if `periodic`
+    /// is set.
+    pub fn new(numbers: &[usize], param: DFTD3Param, periodic: bool) -> Self {
+        Self {
+            numbers: numbers.to_vec(),
+            param,
+            periodic,
+            cutoff: RealspaceCutoff::default(),
+            state: None,
+        }
+    }
+
+    /// Set realspace cutoffs of the model used by this driver
+    pub fn with_realspace_cutoff(mut self, cutoff: RealspaceCutoff) -> Self {
+        self.cutoff = cutoff;
+        self
+    }
+
+    /// Evaluate energy, forces and virial for positions [natoms][3] and lattice [3][3] (failable)
+    pub fn compute_f(
+        &mut self,
+        positions: &[f64],
+        lattice: &[f64],
+    ) -> Result<IpiResult, DFTD3Error> {
+        let lattice = self.periodic.then_some(lattice);
+        match &mut self.state {
+            Some((structure, _)) => structure.update_f(positions, lattice)?,
+            None => {
+                let natoms = self.numbers.len();
+                let structure =
+                    DFTD3Structure::new_f(natoms, &self.numbers, positions, lattice, None)?;
+                let model = DFTD3Model::new_f(&structure)?;
+                model.apply_realspace_cutoff_f(&self.cutoff)?;
+                self.state = Some((structure, model));
+            }
+        }
+        let (structure, model) = self.state.as_ref().unwrap();
+        let (energy, gradient, sigma) =
+            get_dispersion_f(structure, model, &self.param, true, true)?;
+        Ok(IpiResult {
+            energy,
+            forces: gradient.unwrap().iter().map(|x| -x).collect(),
+            virial: sigma.unwrap().iter().map(|x| -x).collect(),
+        })
+    }
+
+    /// Serve i-PI requests on connected stream until server sends `EXIT` (failable)
+    ///
+    /// Returns number of evaluated steps.
+    pub fn run_f<S: Read + Write>(&mut self, stream: &mut S) -> Result<usize, DFTD3Error> {
+        let mut result: Option<IpiResult> = None;
+        let mut nsteps = 0;
+        loop {
+            let header = recv_header(stream).map_err(io_error)?;
+            match header.as_str() {
+                "STATUS" => {
+                    let status = if result.is_some() {
+                        "HAVEDATA"
+                    } else {
+                        "READY"
+                    };
+                    send_header(stream, status).map_err(io_error)?;
+                }
+                "INIT" => {
+                    let _bead = recv_i32(stream).map_err(io_error)?;
+                    let len = recv_i32(stream).map_err(io_error)?;
+                    let mut init = vec![0u8; len.max(0) as usize];
+                    stream.read_exact(&mut init).map_err(io_error)?;
+                }
+                "POSDATA" => {
+                    let cell = recv_f64s(stream, 9).map_err(io_error)?;
+                    let _inverse_cell = recv_f64s(stream, 9).map_err(io_error)?;
+                    let natoms = recv_i32(stream).map_err(io_error)? as usize;
+                    if natoms != self.numbers.len() {
+                        return Err(DFTD3Error::Rust(format!(
+                            "Invalid number of atoms from i-PI server, expected {}, got {}",
+                            self.numbers.len(),
+                            natoms
+                        )));
+                    }
+                    let positions = recv_f64s(stream, 3 * natoms).map_err(io_error)?;
+                    // lattice vectors are columns of cell
+                    result = Some(self.compute_f(&positions, &transpose(&cell))?);
+                    nsteps += 1;
+                }
+                "GETFORCE" => {
+                    let result = result.take().ok_or_else(|| {
+                        DFTD3Error::Rust("i-PI server requested forces before positions".into())
+                    })?;
+                    let natoms = result.forces.len() / 3;
+                    let mut send = || -> std::io::Result<()> {
+                        send_header(stream, "FORCEREADY")?;
+                        send_f64s(stream, &[result.energy])?;
+                        stream.write_all(&(natoms as i32).to_ne_bytes())?;
+                        send_f64s(stream, &result.forces)?;
+                        send_f64s(stream, &transpose(&result.virial))?;
+                        // no extra data
+                        stream.write_all(&0i32.to_ne_bytes())
+                    };
+                    send().map_err(io_error)?;
+                }
+                "EXIT" => return Ok(nsteps),
+                _ => {
+                    return Err(DFTD3Error::Rust(format!(
+                        "Unknown i-PI message {:?}",
+                        header
+                    )))
+                }
+            }
+        }
+    }
+
+    /// Connect to i-PI server and serve requests until `EXIT` (failable)
+    ///
+    /// Returns number of evaluated steps.
+    pub fn connect_f(&mut self, address: &IpiAddress) -> Result<usize, DFTD3Error> {
+        match address {
+            IpiAddress::Tcp(_) => {
+                let mut stream = TcpStream::connect(address.get_path()).map_err(io_error)?;
+                stream.set_nodelay(true).map_err(io_error)?;
+                self.run_f(&mut stream)
+            }
+            #[cfg(unix)]
+            IpiAddress::Unix(_) => {
+                let mut stream = UnixStream::connect(address.get_path()).map_err(io_error)?;
+                self.run_f(&mut stream)
+            }
+            #[cfg(not(unix))]
+            IpiAddress::Unix(_) => Err(DFTD3Error::Rust(
+                "UNIX sockets are not supported on this platform".to_string(),
+            )),
+        }
+    }
+
+    /// Connect to i-PI server and serve requests until `EXIT`
+    pub fn connect(&mut self, address: &IpiAddress) -> usize {
+        self.connect_f(address).unwrap()
+    }
+}
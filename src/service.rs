@@ -0,0 +1,441 @@
+//! Line-delimited JSON service for long-running dispersion evaluations.
+//!
+//! Each line of input is a request `{"id": ..., "method": ..., "params": {...}}`, and each request
+//! is answered by one line `{"id": ..., "result": ...}` or `{"id": ..., "error": {"message": ...}}`.
+//! Clients create sessions holding structure, model and damping parameters, so that library
+//! initialization and parameter loading happen once per session. Errors (including those of the
+//! library) are reported per request and leave the session usable.
+//!
+//! Methods (all quantities in atomic units; matrices as arrays of rows):
+//!
+//! - `create`: `numbers` or `symbols`, `positions` [natoms][3], optional `lattice` [3][3] and
+//!   `periodic` [3], `method`, optional `damping` (default `d3bj`), `atm` and `cutoff`
+//!   (`{"disp2", "disp3", "cn"}`); returns `{"session": id}`
+//! - `update`: `session`, `positions`, optional `lattice`
+//! - `compute`: `session`, optional `gradient` and `sigma` (default true); returns `energy`,
+//!   `gradient` and `sigma`
+//! - `pairwise`: `session`; returns `pair_energy2` and `pair_energy3` [natoms][natoms]
+//! - `close`: `session`
+//! - `shutdown`: stop the service
+
+use crate::prelude::*;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+/// Session of the service
+struct Session {
+    structure: DFTD3Structure,
+    model: DFTD3Model,
+    param: DFTD3Param,
+}
+
+/// Socket file bound by the service, removed on every exit path
+#[cfg(unix)]
+struct SocketFile<'a>(&'a str);
+
+#[cfg(unix)]
+impl Drop for SocketFile<'_> {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(self.0);
+    }
+}
+
+fn invalid(message: String) -> DFTD3Error {
+    DFTD3Error::Rust(message)
+}
+
+fn get_field<'a>(params: &'a Value, key: &str) -> Result<&'a Value, DFTD3Error> {
+    params
+        .get(key)
+        .ok_or_else(|| invalid(format!("Missing field {}", key)))
+}
+
+fn get_bool(params: &Value, key: &str, default: bool) -> Result<bool, DFTD3Error> {
+    match params.get(key) {
+        None => Ok(default),
+        Some(value) => value
+            .as_bool()
+            .ok_or_else(|| invalid(format!("Invalid field {}, expected boolean", key))),
+    }
+}
+
+fn get_f64(params: &Value, key: &str) -> Result<f64, DFTD3Error> {
+    get_field(params, key)?
+        .as_f64()
+        .ok_or_else(|| invalid(format!("Invalid field {}, expected number", key)))
+}
+
+fn get_str<'a>(params: &'a Value, key: &str) -> Result<&'a str, DFTD3Error> {
+    get_field(params, key)?
+        .as_str()
+        .ok_or_else(|| invalid(format!("Invalid field {}, expected string", key)))
+}
+
+fn get_str_or<'a>(params: &'a Value, key: &str, default: &'a str) -> Result<&'a str, DFTD3Error> {
+    match params.get(key) {
+        None => Ok(default),
+        Some(value) => value
+            .as_str()
+            .ok_or_else(|| invalid(format!("Invalid field {}, expected string", key))),
+    }
+}
+
+/// Flatten matrix given as array of rows of 3 numbers.
+fn get_rows(params: &Value, key: &str) -> Result<Vec<f64>, DFTD3Error> {
+    let error = || {
+        invalid(format!(
+            "Invalid field {}, expected array of rows of 3 numbers",
+            key
+        ))
+    };
+    let rows = get_field(params, key)?.as_array().ok_or_else(error)?;
+    let mut values = Vec::with_capacity(3 * rows.len());
+    for row in rows {
+        let row = row.as_array().filter(|x| x.len() == 3).ok_or_else(error)?;
+        for x in row {
+            values.push(x.as_f64().ok_or_else(error)?);
+        }
+    }
+    Ok(values)
+}
+
+fn get_numbers(params: &Value) -> Result<Vec<usize>, DFTD3Error> {
+    if let Some(numbers) = params.get("numbers") {
+        let error = || invalid("Invalid field numbers, expected array of integers".to_string());
+        numbers
+            .as_array()
+            .ok_or_else(error)?
+            .iter()
+            .map(|x| x.as_u64().map(|x| x as usize).ok_or_else(error))
+            .collect()
+    } else {
+        let error = || invalid("Invalid field symbols, expected array of strings".to_string());
+        get_field(params, "symbols")?
+            .as_array()
+            .ok_or_else(error)?
+            .iter()
+            .map(|x| {
+                let symbol = x.as_str().ok_or_else(error)?;
+                element_number(symbol)
+                    .ok_or_else(|| invalid(format!("Unknown element symbol {}", symbol)))
+            })
+            .collect()
+    }
+}
+
+/// Matrix [nrows][ncols] as array of rows.
+fn to_rows(values: &[f64], ncols: usize) -> Value {
+    values.chunks(ncols).map(|x| x.to_vec()).collect()
+}
+
+/// Line-delimited JSON dispersion service
+#[derive(Default)]
+pub struct DFTD3Service {
+    sessions: HashMap<u64, Session>,
+    next_session: u64,
+    shutdown: bool,
+}
+
+impl DFTD3Service {
+    /// Create service without sessions
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get number of open sessions
+    pub fn get_nsessions(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Whether `shutdown` has been requested
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown
+    }
+
+    fn get_session(&mut self, params: &Value) -> Result<&mut Session, DFTD3Error> {
+        let id = get_field(params, "session")?
+            .as_u64()
+            .ok_or_else(|| invalid("Invalid field session, expected integer".to_string()))?;
+        self.sessions
+            .get_mut(&id)
+            .ok_or_else(|| invalid(format!("Unknown session {}", id)))
+    }
+
+    fn create_f(&mut self, params: &Value) -> Result<Value, DFTD3Error> {
+        let numbers = get_numbers(params)?;
+        let positions = get_rows(params, "positions")?;
+        let lattice = match params.get("lattice") {
+            Some(_) => Some(get_rows(params, "lattice")?),
+            None => None,
+        };
+        let periodic = match params.get("periodic") {
+            Some(periodic) => Some(
+                periodic
+                    .as_array()
+                    .and_then(|x| x.iter().map(|x| x.as_bool()).collect::<Option<Vec<_>>>())
+                    .ok_or_else(|| {
+                        invalid("Invalid field periodic, expected array of booleans".to_string())
+                    })?,
+            ),
+            None => None,
+        };
+        let damping = get_str_or(params, "damping", "d3bj")?;
+        let param = DFTD3Param::load_damping_f(
+            damping,
+            get_str(params, "method")?,
+            get_bool(params, "atm", false)?,
+        )?;
+        let structure = DFTD3Structure::new_f(
+            numbers.len(),
+            &numbers,
+            &positions,
+            lattice.as_deref(),
+            periodic.as_deref(),
+        )?;
+        let model = DFTD3Model::new_f(&structure)?;
+        if let Some(cutoff) = params.get("cutoff") {
+            let cutoff = RealspaceCutoff::new_f(
+                get_f64(cutoff, "disp2")?,
+                get_f64(cutoff, "disp3")?,
+                get_f64(cutoff, "cn")?,
+            )?;
+            model.apply_realspace_cutoff_f(&cutoff)?;
+        }
+        let id = self.next_session;
+        self.next_session += 1;
+        self.sessions.insert(
+            id,
+            Session {
+                structure,
+                model,
+                param,
+            },
+        );
+        Ok(json!({ "session": id }))
+    }
+
+    fn update_f(&mut self, params: &Value) -> Result<Value, DFTD3Error> {
+        let positions = get_rows(params, "positions")?;
+        let lattice = match params.get("lattice") {
+            Some(_) => Some(get_rows(params, "lattice")?),
+            None => None,
+        };
+        let session = self.get_session(params)?;
+        session.structure.update_f(&positions, lattice.as_deref())?;
+        Ok(json!({}))
+    }
+
+    fn compute_f(&mut self, params: &Value) -> Result<Value, DFTD3Error> {
+        let eval_grad = get_bool(params, "gradient", true)?;
+        let eval_sigma = get_bool(params, "sigma", true)?;
+        let session = self.get_session(params)?;
+        let (energy, gradient, sigma) = get_dispersion_f(
+            &session.structure,
+            &session.model,
+            &session.param,
+            eval_grad,
+            eval_sigma,
+        )?;
+        let mut result = Map::new();
+        result.insert("energy".to_string(), json!(energy));
+        if let Some(gradient) = gradient {
+            result.insert("gradient".to_string(), to_rows(&gradient, 3));
+        }
+        if let Some(sigma) = sigma {
+            result.insert("sigma".to_string(), to_rows(&sigma, 3));
+        }
+        Ok(Value::Object(result))
+    }
+
+    fn pairwise_f(&mut self, params: &Value) -> Result<Value, DFTD3Error> {
+        let session = self.get_session(params)?;
+        let natoms = session.structure.get_natoms();
+        let (pair_energy2, pair_energy3) =
+            get_pairwise_dispersion_f(&session.structure, &session.model, &session.param)?;
+        Ok(json!({
+            "pair_energy2": to_rows(&pair_energy2, natoms),
+            "pair_energy3": to_rows(&pair_energy3, natoms),
+        }))
+    }
+
+    fn close_f(&mut self, params: &Value) -> Result<Value, DFTD3Error> {
+        self.get_session(params)?;
+        let id = params["session"].as_u64().unwrap();
+        self.sessions.remove(&id);
+        Ok(json!({}))
+    }
+
+    /// Handle one request and return its response
+    pub fn handle_request(&mut self, request: &Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let params = request.get("params").cloned().unwrap_or(json!({}));
+        let result = match request.get("method").and_then(|x| x.as_str()) {
+            Some("create") => self.create_f(&params),
+            Some("update") => self.update_f(&params),
+            Some("compute") => self.compute_f(&params),
+            Some("pairwise") => self.pairwise_f(&params),
+            Some("close") => self.close_f(&params),
+            Some("shutdown") => {
+                self.shutdown = true;
+                Ok(json!({}))
+            }
+            Some(method) => Err(invalid(format!("Unknown method {}", method))),
+            None => Err(invalid("Missing field method".to_string())),
+        };
+        match result {
+            Ok(result) => json!({ "id": id, "result": result }),
+            Err(err) => json!({ "id": id, "error": { "message": err.get_message() } }),
+        }
+    }
+
+    /// Handle one line of input; empty lines are ignored
+    pub fn handle_line(&mut self, line: &str) -> Option<String> {
+        if line.trim().is_empty() {
+            return None;
+        }
+        let response = match serde_json::from_str::<Value>(line) {
+            Ok(request) => self.handle_request(&request),
+            Err(err) => {
+                json!({ "id": null, "error": { "message": format!("Invalid JSON: {}", err) } })
+            }
+        };
+        Some(response.to_string())
+    }
+
+    /// Serve requests line by line until end of input or `shutdown` (failable)
+    pub fn run_f(
+        &mut self,
+        reader: impl BufRead,
+        mut writer: impl Write,
+    ) -> Result<(), DFTD3Error> {
+        let io_error = |err: std::io::Error| invalid(format!("Service I/O error: {}", err));
+        for line in reader.lines() {
+            if let Some(response) = self.handle_line(&line.map_err(io_error)?) {
+                writeln!(writer, "{}", response).map_err(io_error)?;
+                writer.flush().map_err(io_error)?;
+            }
+            if self.shutdown {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Serve requests over standard input and output (failable)
+    pub fn run_stdio_f(&mut self) -> Result<(), DFTD3Error> {
+        self.run_f(std::io::stdin().lock(), std::io::stdout().lock())
+    }
+
+    /// Serve clients connecting to UNIX socket one after another, until `shutdown` (failable)
+    ///
+    /// Sessions are kept across connections.
+    #[cfg(unix)]
+    pub fn run_unix_f(&mut self, path: &str) -> Result<(), DFTD3Error> {
+        let io_error = |err: std::io::Error| invalid(format!("Service I/O error: {}", err));
+        let listener = std::os::unix::net::UnixListener::bind(path).map_err(io_error)?;
+        let _socket = SocketFile(path);
+        for stream in listener.incoming() {
+            let stream = stream.map_err(io_error)?;
+            let reader = std::io::BufReader::new(stream.try_clone().map_err(io_error)?);
+            self.run_f(reader, stream)?;
+            if self.shutdown {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_session() {
+        let input = [
+            r#"{"id": 1, "method": "create", "params": {"symbols": ["O", "H", "H"], "positions": [[0.0, 0.0, 0.0], [1.81, 0.0, 0.0], [-0.45, 1.75, 0.0]], "method": "PBE0"}}"#,
+            r#"{"id": 2, "method": "compute", "params": {"session": 0}}"#,
+            r#"{"id": 3, "method": "update", "params": {"session": 0, "positions": [[0.0, 0.0, 0.0]]}}"#,
+            r#"{"id": 4, "method": "update", "params": {"session": 0, "positions": [[0.0, 0.0, 0.1], [1.81, 0.0, 0.0], [-0.45, 1.75, 0.0]]}}"#,
+            r#"{"id": 5, "method": "pairwise", "params": {"session": 0}}"#,
+            "",
+            r#"{"id": 6, "method": "close", "params": {"session": 0}}"#,
+            r#"{"id": 7, "method": "shutdown"}"#,
+            r#"{"id": 8, "method": "compute", "params": {"session": 0}}"#,
+        ]
+        .join("\n");
+        let mut output = vec![];
+        let mut service = DFTD3Service::new();
+        service.run_f(input.as_bytes(), &mut output).unwrap();
+        let responses = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|x| serde_json::from_str::<Value>(x).unwrap())
+            .collect::<Vec<_>>();
+        // requests after shutdown are not handled
+        assert_eq!(responses.len(), 7);
+        assert!(service.is_shutdown() && service.get_nsessions() == 0);
+
+        // reference by direct evaluation
+        let numbers = [8, 1, 1];
+        let positions = [0.0, 0.0, 0.0, 1.81, 0.0, 0.0, -0.45, 1.75, 0.0];
+        let structure = DFTD3Structure::new(3, &numbers, &positions, None, None);
+        let model = DFTD3Model::new(&structure);
+        let param = DFTD3Param::load_rational_damping("PBE0", false);
+        let (energy, gradient, _) = get_dispersion(&structure, &model, &param, true, false);
+        let result = &responses[1]["result"];
+        assert!((result["energy"].as_f64().unwrap() - energy).abs() < 1e-12);
+        assert!((result["gradient"][1][0].as_f64().unwrap() - gradient.unwrap()[3]).abs() < 1e-12);
+
+        // invalid update is reported and session stays usable
+        assert_eq!(responses[2]["id"], 3);
+        assert!(responses[2]["error"]["message"].is_string());
+        assert!(responses[3]["result"].is_object());
+        assert_eq!(
+            responses[4]["result"]["pair_energy2"][0]
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_service_invalid_damping() {
+        let mut service = DFTD3Service::new();
+        let request = r#"{"id": 1, "method": "create", "params": {"symbols": ["He"], "positions": [[0.0, 0.0, 0.0]], "method": "PBE0", "damping": 3}}"#;
+        let response = service.handle_line(request).unwrap();
+        let response = serde_json::from_str::<Value>(&response).unwrap();
+        assert_eq!(
+            response["error"]["message"],
+            "Invalid field damping, expected string"
+        );
+        assert_eq!(service.get_nsessions(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_service_unix_socket_removed() {
+        let path = std::env::temp_dir().join(format!("dftd3-service-{}.sock", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let server = {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                DFTD3Service::new()
+                    .run_unix_f(&path)
+                    .map_err(|err| err.get_message())
+            })
+        };
+        let mut stream = loop {
+            match std::os::unix::net::UnixStream::connect(&path) {
+                Ok(stream) => break stream,
+                Err(_) => std::thread::sleep(std::time::Duration::from_millis(10)),
+            }
+        };
+        // invalid UTF-8 stops the service with an error, which still removes the socket file
+        stream.write_all(&[0xff, b'\n']).unwrap();
+        assert!(server.join().unwrap().is_err());
+        assert!(!std::path::Path::new(&path).exists());
+    }
+}
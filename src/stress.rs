@@ -0,0 +1,205 @@
+//! Stress tensor, pressure and lattice gradient from sigma (virial) of periodic systems.
+//!
+//! Sigma from [`get_dispersion`] is the derivative of energy with respect to homogeneous strain
+//! `r' = (1 + eps) r`, i.e. `sigma[a][b] = dE / d eps[a][b]`. Stress follows the convention of
+//! ASE, `stress = sigma / V`, so that positive diagonal stress is tensile, and pressure is
+//! `P = -tr(stress) / 3`.
+
+use crate::prelude::*;
+
+/// Conversion factor of stress from Hartree/Bohr^3 to eV/Angstrom^3
+pub const HARTREE_PER_BOHR3_TO_EV_PER_ANGSTROM3: f64 =
+    HARTREE_TO_EV / (BOHR_TO_ANGSTROM * BOHR_TO_ANGSTROM * BOHR_TO_ANGSTROM);
+
+/// Conversion factor of stress from Hartree/Bohr^3 to GPa (1 eV/Angstrom^3 = 160.2176634 GPa)
+pub const HARTREE_PER_BOHR3_TO_GPA: f64 = HARTREE_PER_BOHR3_TO_EV_PER_ANGSTROM3 * 160.2176634;
+
+/// Determinant of 3x3 matrix (row-major)
+fn determinant(m: &[f64]) -> f64 {
+    m[0] * (m[4] * m[8] - m[5] * m[7]) - m[1] * (m[3] * m[8] - m[5] * m[6])
+        + m[2] * (m[3] * m[7] - m[4] * m[6])
+}
+
+/// Inverse of 3x3 matrix (row-major)
+pub(crate) fn inverse(m: &[f64]) -> Option<[f64; 9]> {
+    let det = determinant(m);
+    if det.abs() < f64::EPSILON {
+        return None;
+    }
+    let cofactor = |i: usize, j: usize| {
+        let (i1, i2) = ((i + 1) % 3, (i + 2) % 3);
+        let (j1, j2) = ((j + 1) % 3, (j + 2) % 3);
+        m[3 * i1 + j1] * m[3 * i2 + j2] - m[3 * i1 + j2] * m[3 * i2 + j1]
+    };
+    let mut inv = [0.0; 9];
+    for i in 0..3 {
+        for j in 0..3 {
+            inv[3 * j + i] = cofactor(i, j) / det;
+        }
+    }
+    Some(inv)
+}
+
+impl DFTD3Structure {
+    /// Cell volume (in Bohr^3), if lattice is given (failable)
+    pub fn get_volume_f(&self) -> Result<f64, DFTD3Error> {
+        match self.get_lattice() {
+            Some(lattice) => Ok(determinant(&lattice).abs()),
+            None => Err(DFTD3Error::Rust(
+                "Cell volume requires lattice of periodic structure".to_string(),
+            )),
+        }
+    }
+
+    /// Cell volume (in Bohr^3), if lattice is given
+    pub fn get_volume(&self) -> f64 {
+        self.get_volume_f().unwrap()
+    }
+}
+
+/// Stress tensor of periodic system
+#[derive(Debug, Clone, PartialEq)]
+pub struct DFTD3Stress {
+    /// cell volume (in Bohr^3)
+    pub volume: f64,
+    /// stress tensor [3][3] (in Hartree/Bohr^3)
+    pub stress: Vec<f64>,
+    /// gradient of energy with respect to lattice vectors at fixed fractional coordinates
+    /// [3][3] (in Hartree/Bohr), each row corresponds to a lattice vector
+    pub lattice_gradient: Vec<f64>,
+}
+
+impl DFTD3Stress {
+    /// Create stress from sigma [3][3] and structure (failable)
+    pub fn from_sigma_f(structure: &DFTD3Structure, sigma: &[f64]) -> Result<Self, DFTD3Error> {
+        if sigma.len() != 9 {
+            return Err(DFTD3Error::Rust(format!(
+                "Invalid dimension for sigma, expected 9, got {}",
+                sigma.len()
+            )));
+        }
+        let volume = structure.get_volume_f()?;
+        let lattice = structure.get_lattice().unwrap();
+        let stress = sigma.iter().map(|x| x / volume).collect();
+        // sigma = G^T L, so that G[k][a] = sum_b sigma[a][b] L^-1[b][k]
+        let inv =
+            inverse(&lattice).ok_or_else(|| DFTD3Error::Rust("Lattice is singular".to_string()))?;
+        let mut lattice_gradient = vec![0.0; 9];
+        for k in 0..3 {
+            for a in 0..3 {
+                lattice_gradient[3 * k + a] =
+                    (0..3).map(|b| sigma[3 * a + b] * inv[3 * b + k]).sum();
+            }
+        }
+        Ok(Self {
+            volume,
+            stress,
+            lattice_gradient,
+        })
+    }
+
+    /// Create stress from sigma [3][3] and structure
+    pub fn from_sigma(structure: &DFTD3Structure, sigma: &[f64]) -> Self {
+        Self::from_sigma_f(structure, sigma).unwrap()
+    }
+
+    /// Evaluate dispersion sigma and convert to stress (failable)
+    pub fn new_f(
+        structure: &DFTD3Structure,
+        model: &DFTD3Model,
+        param: &DFTD3Param,
+    ) -> Result<Self, DFTD3Error> {
+        let (_, _, sigma) = get_dispersion_f(structure, model, param, false, true)?;
+        Self::from_sigma_f(structure, &sigma.unwrap())
+    }
+
+    /// Evaluate dispersion sigma and convert to stress
+    pub fn new(structure: &DFTD3Structure, model: &DFTD3Model, param: &DFTD3Param) -> Self {
+        Self::new_f(structure, model, param).unwrap()
+    }
+
+    /// Scalar pressure (in Hartree/Bohr^3)
+    pub fn pressure(&self) -> f64 {
+        -(self.stress[0] + self.stress[4] + self.stress[8]) / 3.0
+    }
+
+    /// Stress tensor [3][3] in GPa
+    pub fn stress_gpa(&self) -> Vec<f64> {
+        self.stress
+            .iter()
+            .map(|x| x * HARTREE_PER_BOHR3_TO_GPA)
+            .collect()
+    }
+
+    /// Scalar pressure in GPa
+    pub fn pressure_gpa(&self) -> f64 {
+        self.pressure() * HARTREE_PER_BOHR3_TO_GPA
+    }
+
+    /// Stress tensor [3][3] in eV/Angstrom^3
+    pub fn stress_ev_per_angstrom3(&self) -> Vec<f64> {
+        self.stress
+            .iter()
+            .map(|x| x * HARTREE_PER_BOHR3_TO_EV_PER_ANGSTROM3)
+            .collect()
+    }
+
+    /// Scalar pressure in eV/Angstrom^3
+    pub fn pressure_ev_per_angstrom3(&self) -> f64 {
+        self.pressure() * HARTREE_PER_BOHR3_TO_EV_PER_ANGSTROM3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Simple cubic cell of CsCl type with lattice constant `a` (in Bohr).
+    fn cubic(a: f64) -> DFTD3Structure {
+        let lattice = [a, 0.0, 0.0, 0.0, a, 0.0, 0.0, 0.0, a];
+        let positions = [0.0, 0.0, 0.0, 0.5 * a, 0.5 * a, 0.5 * a];
+        let periodic = [true, true, true];
+        DFTD3Structure::new(2, &[55, 17], &positions, Some(&lattice), Some(&periodic))
+    }
+
+    #[test]
+    fn test_cubic_stress() {
+        let a = 7.8;
+        let param = DFTD3Param::load_rational_damping("PBE", false);
+        let energy = |a: f64| {
+            let structure = cubic(a);
+            let model = DFTD3Model::new(&structure);
+            get_dispersion(&structure, &model, &param, false, false).0
+        };
+
+        let structure = cubic(a);
+        let model = DFTD3Model::new(&structure);
+        assert!((structure.get_volume() - a.powi(3)).abs() < 1e-10);
+        let stress = DFTD3Stress::new(&structure, &model, &param);
+
+        // isotropic stress
+        let s = &stress.stress;
+        assert!((s[0] - s[4]).abs() < 1e-12 && (s[0] - s[8]).abs() < 1e-12);
+        assert!(s[1].abs() < 1e-12 && s[2].abs() < 1e-12 && s[5].abs() < 1e-12);
+
+        // pressure P = -dE/dV
+        let h = 1.0e-4;
+        let de_dv = (energy(a + h) - energy(a - h)) / (2.0 * h * 3.0 * a * a);
+        assert!((stress.pressure() + de_dv).abs() < 1e-9);
+        // dispersion is attractive, so pressure is negative
+        assert!(stress.pressure_gpa() < 0.0);
+
+        // lattice gradient dE/dL[0][0] at fixed fractional coordinates
+        let energy_l00 = |l00: f64| {
+            let lattice = [l00, 0.0, 0.0, 0.0, a, 0.0, 0.0, 0.0, a];
+            let positions = [0.0, 0.0, 0.0, 0.5 * l00, 0.5 * a, 0.5 * a];
+            let periodic = [true, true, true];
+            let structure =
+                DFTD3Structure::new(2, &[55, 17], &positions, Some(&lattice), Some(&periodic));
+            let model = DFTD3Model::new(&structure);
+            get_dispersion(&structure, &model, &param, false, false).0
+        };
+        let reference = (energy_l00(a + h) - energy_l00(a - h)) / (2.0 * h);
+        assert!((stress.lattice_gradient[0] - reference).abs() < 1e-8);
+    }
+}
@@ -0,0 +1,585 @@
+//! Dispersion along XYZ and extended XYZ trajectories.
+//!
+//! [`XyzReader`] streams frames of a multi-frame (extended) XYZ file, with positions and lattice
+//! in Angstrom converted to Bohr. [`evaluate_trajectory_f`] evaluates dispersion energy, maximum
+//! force and pressure (periodic frames) of each frame. One [`DFTD3Structure`] and [`DFTD3Model`]
+//! are reused through [`DFTD3Structure::update_f`] as long as elements and periodicity of frames
+//! stay the same, and recreated otherwise. Frames can be evaluated in parallel chunks, each thread
+//! reusing its own structure.
+
+use crate::prelude::*;
+use std::io::{BufRead, Write};
+
+/// One frame of (extended) XYZ trajectory
+#[derive(Debug, Clone, PartialEq)]
+pub struct XyzFrame {
+    /// atomic numbers [natoms]
+    pub numbers: Vec<usize>,
+    /// positions [natoms][3] (in Bohr)
+    pub positions: Vec<f64>,
+    /// lattice [3][3] (in Bohr), from `Lattice` of extended XYZ
+    pub lattice: Option<Vec<f64>>,
+    /// periodicity [3], from `pbc` of extended XYZ
+    pub periodic: Option<Vec<bool>>,
+}
+
+/// Split comment line of extended XYZ into `key=value` pairs (values may be quoted)
+fn parse_comment(comment: &str) -> Vec<(String, String)> {
+    let mut pairs = vec![];
+    let mut chars = comment.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let key = std::iter::from_fn(|| chars.next_if(|c| *c != '=' && !c.is_whitespace()))
+            .collect::<String>();
+        if key.is_empty() {
+            break;
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            continue;
+        }
+        let value = match chars.next_if_eq(&'"') {
+            Some(_) => {
+                let value = std::iter::from_fn(|| chars.next_if(|c| *c != '"')).collect();
+                chars.next();
+                value
+            }
+            None => std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace())).collect(),
+        };
+        pairs.push((key, value));
+    }
+    pairs
+}
+
+/// Columns of species and positions from `Properties` of extended XYZ
+fn parse_properties(properties: &str) -> Result<(usize, usize), DFTD3Error> {
+    let fields = properties.split(':').collect::<Vec<_>>();
+    let (mut species, mut pos) = (None, None);
+    let mut column = 0;
+    for field in fields.chunks(3) {
+        let [name, _, count] = field else {
+            return Err(DFTD3Error::Rust(format!(
+                "Invalid XYZ properties {}",
+                properties
+            )));
+        };
+        match name.to_lowercase().as_str() {
+            "species" => species = Some(column),
+            "pos" => pos = Some(column),
+            _ => (),
+        }
+        column += count
+            .parse::<usize>()
+            .map_err(|_| DFTD3Error::Rust(format!("Invalid XYZ properties {}", properties)))?;
+    }
+    species.zip(pos).ok_or_else(|| {
+        DFTD3Error::Rust(format!(
+            "Missing species or pos in XYZ properties {}",
+            properties
+        ))
+    })
+}
+
+/// Streaming reader of (extended) XYZ trajectory
+pub struct XyzReader<R: BufRead> {
+    reader: R,
+    line: usize,
+}
+
+impl<R: BufRead> XyzReader<R> {
+    /// Create reader of (extended) XYZ trajectory
+    pub fn new(reader: R) -> Self {
+        Self { reader, line: 0 }
+    }
+
+    fn read_line(&mut self) -> Result<Option<String>, DFTD3Error> {
+        let mut line = String::new();
+        let nread = self
+            .reader
+            .read_line(&mut line)
+            .map_err(|err| DFTD3Error::Rust(format!("Trajectory I/O error: {}", err)))?;
+        self.line += 1;
+        Ok((nread > 0).then_some(line))
+    }
+
+    fn error(&self, message: &str) -> DFTD3Error {
+        DFTD3Error::Rust(format!("Invalid XYZ at line {}: {}", self.line, message))
+    }
+
+    /// Read next frame; `None` at end of input (failable)
+    pub fn read_frame_f(&mut self) -> Result<Option<XyzFrame>, DFTD3Error> {
+        // natoms line, skipping blank lines between frames
+        let natoms = loop {
+            match self.read_line()? {
+                None => return Ok(None),
+                Some(line) if line.trim().is_empty() => continue,
+                Some(line) => {
+                    break line
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_| self.error("expected number of atoms"))?
+                }
+            }
+        };
+        let comment = self
+            .read_line()?
+            .ok_or_else(|| self.error("missing comment line"))?;
+        let mut lattice = None;
+        let mut periodic = None;
+        let (mut species, mut pos) = (0, 1);
+        for (key, value) in parse_comment(&comment) {
+            match key.to_lowercase().as_str() {
+                "lattice" => {
+                    let values = value
+                        .split_whitespace()
+                        .map(|x| x.parse::<f64>().map(|x| x / BOHR_TO_ANGSTROM))
+                        .collect::<Result<Vec<f64>, _>>()
+                        .ok()
+                        .filter(|x| x.len() == 9)
+                        .ok_or_else(|| self.error("expected 9 numbers of lattice"))?;
+                    lattice = Some(values);
+                }
+                "pbc" => {
+                    let values = value
+                        .split_whitespace()
+                        .map(|x| match x {
+                            "T" | "t" | "True" | "true" | "1" => Some(true),
+                            "F" | "f" | "False" | "false" | "0" => Some(false),
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<bool>>>()
+                        .filter(|x| x.len() == 3)
+                        .ok_or_else(|| self.error("expected 3 booleans of pbc"))?;
+                    periodic = Some(values);
+                }
+                "properties" => (species, pos) = parse_properties(&value)?,
+                _ => (),
+            }
+        }
+
+        let mut numbers = Vec::with_capacity(natoms);
+        let mut positions = Vec::with_capacity(3 * natoms);
+        for _ in 0..natoms {
+            let line = self
+                .read_line()?
+                .ok_or_else(|| self.error("unexpected end of frame"))?;
+            let columns = line.split_whitespace().collect::<Vec<_>>();
+            let symbol = columns
+                .get(species)
+                .ok_or_else(|| self.error("missing species"))?;
+            let number = match symbol.parse::<usize>() {
+                Ok(number) => number,
+                Err(_) => element_number(symbol)
+                    .ok_or_else(|| self.error(&format!("unknown element symbol {}", symbol)))?,
+            };
+            numbers.push(number);
+            for k in 0..3 {
+                let x = columns
+                    .get(pos + k)
+                    .and_then(|x| x.parse::<f64>().ok())
+                    .ok_or_else(|| self.error("expected 3 numbers of position"))?;
+                positions.push(x / BOHR_TO_ANGSTROM);
+            }
+        }
+        Ok(Some(XyzFrame {
+            numbers,
+            positions,
+            lattice,
+            periodic,
+        }))
+    }
+}
+
+impl<R: BufRead> Iterator for XyzReader<R> {
+    type Item = Result<XyzFrame, DFTD3Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame_f().transpose()
+    }
+}
+
+/// Write one frame in extended XYZ format (positions and lattice in Angstrom)
+///
+/// `properties` are written to the comment line after lattice and periodicity (`pbc`, periodic in
+/// all directions if not given), e.g. `energy=...`.
+pub(crate) fn write_extxyz_frame(
+    writer: &mut impl Write,
+    numbers: &[usize],
+    positions: &[f64],
+    lattice: Option<&[f64]>,
+    periodic: Option<&[bool]>,
+    properties: &str,
+) -> std::io::Result<()> {
+    writeln!(writer, "{}", numbers.len())?;
+    let mut comment = String::new();
+    if let Some(lattice) = lattice {
+        let lattice = lattice
+            .iter()
+            .map(|x| format!("{:.8}", x * BOHR_TO_ANGSTROM))
+            .collect::<Vec<_>>();
+        let pbc = periodic
+            .unwrap_or(&[true; 3])
+            .iter()
+            .map(|&x| if x { "T" } else { "F" })
+            .collect::<Vec<_>>();
+        comment = format!(
+            "Lattice=\"{}\" pbc=\"{}\" ",
+            lattice.join(" "),
+            pbc.join(" ")
+        );
+    }
+    writeln!(
+        writer,
+        "{}{} Properties=species:S:1:pos:R:3",
+        comment, properties
+    )?;
+    for (number, position) in numbers.iter().zip(positions.chunks(3)) {
+        let symbol = element_symbol(*number).unwrap_or("X");
+        let [x, y, z] = [0, 1, 2].map(|k| position[k] * BOHR_TO_ANGSTROM);
+        writeln!(writer, "{:<2} {:16.10} {:16.10} {:16.10}", symbol, x, y, z)?;
+    }
+    Ok(())
+}
+
+/// Dispersion result of one trajectory frame (atomic units)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameResult {
+    /// index of frame in trajectory
+    pub frame: usize,
+    /// number of atoms
+    pub natoms: usize,
+    /// dispersion energy (in Hartree)
+    pub energy: f64,
+    /// maximum norm of atomic forces (in Hartree/Bohr)
+    pub max_force: f64,
+    /// pressure (in Hartree/Bohr^3), for frames with lattice
+    pub pressure: Option<f64>,
+}
+
+/// Evaluator of trajectory frames reusing structure and model between frames
+pub struct TrajectoryEvaluator<'a> {
+    param: &'a DFTD3Param,
+    cutoff: RealspaceCutoff,
+    state: Option<(DFTD3Structure, DFTD3Model)>,
+}
+
+impl<'a> TrajectoryEvaluator<'a> {
+    /// Create evaluator with damping parameters and realspace cutoffs of the model
+    pub fn new(param: &'a DFTD3Param, cutoff: RealspaceCutoff) -> Self {
+        Self {
+            param,
+            cutoff,
+            state: None,
+        }
+    }
+
+    /// Evaluate dispersion of frame (failable)
+    ///
+    /// The cached structure is updated if elements and periodicity equal those of the previous
+    /// frame, and recreated otherwise.
+    pub fn evaluate_f(
+        &mut self,
+        index: usize,
+        frame: &XyzFrame,
+    ) -> Result<FrameResult, DFTD3Error> {
+        let reusable = self.state.as_ref().is_some_and(|(structure, _)| {
+            structure.get_numbers() == frame.numbers
+                && structure.get_lattice().is_some() == frame.lattice.is_some()
+                && structure.get_periodic() == frame.periodic.as_deref()
+        });
+        if reusable {
+            let (structure, _) = self.state.as_mut().unwrap();
+            structure.update_f(&frame.positions, frame.lattice.as_deref())?;
+        } else {
+            let structure = DFTD3Structure::new_f(
+                frame.numbers.len(),
+                &frame.numbers,
+                &frame.positions,
+                frame.lattice.as_deref(),
+                frame.periodic.as_deref(),
+            )?;
+            let model = DFTD3Model::new_f(&structure)?;
+            model.apply_realspace_cutoff_f(&self.cutoff)?;
+            self.state = Some((structure, model));
+        }
+
+        let (structure, model) = self.state.as_ref().unwrap();
+        let periodic = frame.lattice.is_some();
+        let (energy, gradient, sigma) =
+            get_dispersion_f(structure, model, self.param, true, periodic)?;
+        let max_force = gradient
+            .unwrap()
+            .chunks(3)
+            .map(|g| (g[0] * g[0] + g[1] * g[1] + g[2] * g[2]).sqrt())
+            .fold(0.0, f64::max);
+        let pressure = match sigma {
+            Some(sigma) => Some(DFTD3Stress::from_sigma_f(structure, &sigma)?.pressure()),
+            None => None,
+        };
+        Ok(FrameResult {
+            frame: index,
+            natoms: frame.numbers.len(),
+            energy,
+            max_force,
+            pressure,
+        })
+    }
+}
+
+/// Options of trajectory evaluation
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrajectoryOptions {
+    /// realspace cutoffs of the model
+    pub cutoff: RealspaceCutoff,
+    /// number of threads; serial evaluation if not larger than 1
+    pub nthreads: usize,
+    /// number of frames read and evaluated at once in parallel evaluation
+    pub chunk_size: usize,
+}
+
+impl Default for TrajectoryOptions {
+    fn default() -> Self {
+        Self {
+            cutoff: RealspaceCutoff::default(),
+            nthreads: 1,
+            chunk_size: 256,
+        }
+    }
+}
+
+/// Evaluate dispersion of each frame of (extended) XYZ trajectory (failable)
+///
+/// Results are passed to `sink` in the order of frames. In parallel evaluation, each chunk of
+/// frames is split into contiguous parts, one per thread, and each thread keeps its own
+/// [`TrajectoryEvaluator`] across chunks. Returns number of frames.
+pub fn evaluate_trajectory_f(
+    reader: impl BufRead,
+    param: &DFTD3Param,
+    options: &TrajectoryOptions,
+    mut sink: impl FnMut(FrameResult) -> Result<(), DFTD3Error>,
+) -> Result<usize, DFTD3Error> {
+    let mut frames = XyzReader::new(reader);
+    let nthreads = options.nthreads.max(1);
+    let mut evaluators = (0..nthreads)
+        .map(|_| TrajectoryEvaluator::new(param, options.cutoff))
+        .collect::<Vec<_>>();
+    let mut nframes = 0;
+    if nthreads == 1 {
+        for frame in frames {
+            sink(evaluators[0].evaluate_f(nframes, &frame?)?)?;
+            nframes += 1;
+        }
+        return Ok(nframes);
+    }
+
+    loop {
+        let chunk = frames
+            .by_ref()
+            .take(options.chunk_size.max(nthreads))
+            .collect::<Result<Vec<XyzFrame>, DFTD3Error>>()?;
+        if chunk.is_empty() {
+            return Ok(nframes);
+        }
+        let part = chunk.len().div_ceil(nthreads);
+        let offset = nframes;
+        let results = std::thread::scope(|scope| {
+            let handles = evaluators
+                .iter_mut()
+                .zip(chunk.chunks(part))
+                .enumerate()
+                .map(|(tid, (evaluator, frames))| {
+                    scope.spawn(move || {
+                        frames
+                            .iter()
+                            .enumerate()
+                            .map(|(i, frame)| evaluator.evaluate_f(offset + tid * part + i, frame))
+                            .collect::<Result<Vec<_>, DFTD3Error>>()
+                            .map_err(|err| err.get_message())
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Result<Vec<_>, String>>()
+        })
+        .map_err(DFTD3Error::Rust)?;
+        for result in results.into_iter().flatten() {
+            sink(result)?;
+        }
+        nframes += chunk.len();
+    }
+}
+
+/// Output format of trajectory results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrajectoryFormat {
+    /// comma-separated values with header line
+    CSV,
+    /// JSON array of objects
+    JSON,
+}
+
+/// Streaming writer of trajectory results (energy in Hartree, max force in Hartree/Bohr,
+/// pressure in GPa)
+pub struct TrajectoryWriter<W: Write> {
+    writer: W,
+    format: TrajectoryFormat,
+    nframes: usize,
+}
+
+impl<W: Write> TrajectoryWriter<W> {
+    /// Create writer of given format
+    pub fn new(writer: W, format: TrajectoryFormat) -> Self {
+        Self {
+            writer,
+            format,
+            nframes: 0,
+        }
+    }
+
+    /// Write result of one frame (failable)
+    pub fn write_f(&mut self, result: &FrameResult) -> Result<(), DFTD3Error> {
+        let pressure = result.pressure.map(|x| x * HARTREE_PER_BOHR3_TO_GPA);
+        let output = match self.format {
+            TrajectoryFormat::CSV => {
+                let header = match self.nframes {
+                    0 => "frame,natoms,energy,max_force,pressure\n",
+                    _ => "",
+                };
+                let pressure = pressure.map_or(String::new(), |x| format!("{:.12e}", x));
+                format!(
+                    "{}{},{},{:.12e},{:.12e},{}\n",
+                    header, result.frame, result.natoms, result.energy, result.max_force, pressure
+                )
+            }
+            TrajectoryFormat::JSON => {
+                let separator = match self.nframes {
+                    0 => "[\n",
+                    _ => ",\n",
+                };
+                let pressure = pressure.map_or("null".to_string(), |x| format!("{:.12e}", x));
+                format!(
+                    "{}  {{\"frame\": {}, \"natoms\": {}, \"energy\": {:.12e}, \"max_force\": {:.12e}, \"pressure\": {}}}",
+                    separator, result.frame, result.natoms, result.energy, result.max_force, pressure
+                )
+            }
+        };
+        self.nframes += 1;
+        self.writer
+            .write_all(output.as_bytes())
+            .map_err(|err| DFTD3Error::Rust(format!("Trajectory I/O error: {}", err)))
+    }
+
+    /// Finish output and flush (failable)
+    pub fn finish_f(mut self) -> Result<W, DFTD3Error> {
+        let footer = match (self.format, self.nframes) {
+            (TrajectoryFormat::CSV, _) => "",
+            (TrajectoryFormat::JSON, 0) => "[]\n",
+            (TrajectoryFormat::JSON, _) => "\n]\n",
+        };
+        let io_error =
+            |err: std::io::Error| DFTD3Error::Rust(format!("Trajectory I/O error: {}", err));
+        self.writer.write_all(footer.as_bytes()).map_err(io_error)?;
+        self.writer.flush().map_err(io_error)?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trajectory() {
+        // two water frames, one water dimer frame, and a periodic frame
+        let input = "\
+3
+frame 0
+O  0.000  0.000  0.000
+H  0.958  0.000  0.000
+H -0.240  0.927  0.000
+3
+Properties=species:S:1:pos:R:3 energy=-1.0
+O  0.000  0.000  0.050
+H  0.958  0.000  0.000
+H -0.240  0.927  0.000
+
+6
+dimer
+8  0.000  0.000  0.000
+1  0.958  0.000  0.000
+1 -0.240  0.927  0.000
+8  2.960  0.000  0.000
+1  3.280  0.900  0.000
+1  3.280 -0.900  0.000
+2
+Lattice=\"2.82 0.0 0.0 0.0 2.82 0.0 0.0 0.0 2.82\" Properties=species:S:1:pos:R:3 pbc=\"T T T\"
+Na 0.00 0.00 0.00
+Cl 1.41 1.41 1.41
+";
+        let frames = XyzReader::new(input.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[2].numbers, [8, 1, 1, 8, 1, 1]);
+        assert_eq!(frames[3].periodic.as_deref(), Some(&[true; 3][..]));
+        assert!((frames[3].lattice.as_ref().unwrap()[0] - 2.82 / BOHR_TO_ANGSTROM).abs() < 1e-12);
+
+        // periodicity of written frame is read back
+        let mut output = vec![];
+        let lattice = frames[3].lattice.as_deref();
+        let periodic = [true, true, false];
+        write_extxyz_frame(
+            &mut output,
+            &frames[3].numbers,
+            &frames[3].positions,
+            lattice,
+            Some(&periodic),
+            "",
+        )
+        .unwrap();
+        let frame = XyzReader::new(output.as_slice()).next().unwrap().unwrap();
+        assert_eq!(frame.periodic.as_deref(), Some(&periodic[..]));
+
+        let param = DFTD3Param::load_rational_damping("PBE0", false);
+        let reference = frames
+            .iter()
+            .map(|frame| {
+                let structure = DFTD3Structure::new(
+                    frame.numbers.len(),
+                    &frame.numbers,
+                    &frame.positions,
+                    frame.lattice.as_deref(),
+                    frame.periodic.as_deref(),
+                );
+                let model = DFTD3Model::new(&structure);
+                get_dispersion(&structure, &model, &param, false, false).0
+            })
+            .collect::<Vec<_>>();
+
+        for nthreads in [1, 2] {
+            let options = TrajectoryOptions {
+                nthreads,
+                chunk_size: 3,
+                ..Default::default()
+            };
+            let mut results = vec![];
+            let nframes = evaluate_trajectory_f(input.as_bytes(), &param, &options, |result| {
+                results.push(result);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(nframes, 4);
+            for (i, result) in results.iter().enumerate() {
+                assert_eq!(result.frame, i);
+                assert!((result.energy - reference[i]).abs() < 1e-12);
+            }
+            assert!(results[3].pressure.is_some() && results[0].pressure.is_none());
+
+            let mut writer = TrajectoryWriter::new(vec![], TrajectoryFormat::CSV);
+            results.iter().for_each(|x| writer.write_f(x).unwrap());
+            let output = String::from_utf8(writer.finish_f().unwrap()).unwrap();
+            assert_eq!(output.lines().count(), 5);
+        }
+    }
+}
@@ -0,0 +1,420 @@
+//! Unit system for inputs and outputs.
+//!
+//! The C library works in atomic units (Bohr for length, Hartree for energy). Functions in this
+//! module convert inputs (positions, lattice, realspace cutoffs) from and results (energy,
+//! gradient, sigma, pairwise energies, interaction energies, Hessian, stress, derivative checks and
+//! structure positions) to the unit system specified by [`Units`]. Functions without `_with_units`
+//! or `in_units` work in atomic units.
+
+use crate::prelude::*;
+
+/// Bohr to Angstrom (CODATA 2018)
+pub const BOHR_TO_ANGSTROM: f64 = 0.529177210903;
+
+/// Hartree to eV (CODATA 2018)
+pub const HARTREE_TO_EV: f64 = 27.211386245988;
+
+/// Hartree to kJ/mol (CODATA 2018)
+pub const HARTREE_TO_KJ_PER_MOL: f64 = 2625.4996394799;
+
+/// Hartree to kcal/mol (CODATA 2018)
+pub const HARTREE_TO_KCAL_PER_MOL: f64 = 627.5094740631;
+
+/// Atomic unit of time to femtoseconds (CODATA 2018)
+pub const AU_TIME_TO_FS: f64 = 0.024188843265857;
+
+/// Dalton (unified atomic mass unit) to electron mass (CODATA 2018)
+pub const DALTON_TO_ELECTRON_MASS: f64 = 1822.888486209;
+
+/// Boltzmann constant in Hartree/K (CODATA 2018)
+pub const BOLTZMANN_HARTREE_PER_KELVIN: f64 = 3.1668115634556e-6;
+
+/// Unit of length
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LengthUnit {
+    #[default]
+    Bohr,
+    Angstrom,
+}
+
+/// Unit of energy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EnergyUnit {
+    #[default]
+    Hartree,
+    EV,
+    KcalPerMol,
+    KJPerMol,
+}
+
+impl LengthUnit {
+    /// Conversion factor from this unit to Bohr
+    pub fn to_bohr(&self) -> f64 {
+        match self {
+            LengthUnit::Bohr => 1.0,
+            LengthUnit::Angstrom => 1.0 / BOHR_TO_ANGSTROM,
+        }
+    }
+}
+
+impl EnergyUnit {
+    /// Conversion factor from Hartree to this unit (amount of this unit per Hartree)
+    pub fn per_hartree(&self) -> f64 {
+        match self {
+            EnergyUnit::Hartree => 1.0,
+            EnergyUnit::EV => HARTREE_TO_EV,
+            EnergyUnit::KcalPerMol => HARTREE_TO_KCAL_PER_MOL,
+            EnergyUnit::KJPerMol => HARTREE_TO_KJ_PER_MOL,
+        }
+    }
+}
+
+/// Unit system of length and energy
+///
+/// Default is atomic units (Bohr, Hartree).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Units {
+    pub length: LengthUnit,
+    pub energy: EnergyUnit,
+}
+
+impl Units {
+    /// Atomic units (Bohr, Hartree)
+    pub const ATOMIC: Units = Units {
+        length: LengthUnit::Bohr,
+        energy: EnergyUnit::Hartree,
+    };
+
+    /// Angstrom and eV
+    pub const ANGSTROM_EV: Units = Units {
+        length: LengthUnit::Angstrom,
+        energy: EnergyUnit::EV,
+    };
+
+    /// Create unit system
+    pub fn new(length: LengthUnit, energy: EnergyUnit) -> Self {
+        Self { length, energy }
+    }
+
+    /// Convert lengths of this unit to Bohr
+    pub fn length_to_bohr(&self, values: &[f64]) -> Vec<f64> {
+        let factor = self.length.to_bohr();
+        values.iter().map(|x| x * factor).collect()
+    }
+
+    /// Convert lengths in Bohr to this unit
+    pub fn length_from_bohr(&self, values: &[f64]) -> Vec<f64> {
+        let factor = self.length.to_bohr();
+        values.iter().map(|x| x / factor).collect()
+    }
+
+    /// Convert energy in Hartree to this unit
+    pub fn energy_from_hartree(&self, value: f64) -> f64 {
+        value * self.energy.per_hartree()
+    }
+
+    /// Convert gradient in Hartree/Bohr to this unit (energy/length)
+    pub fn gradient_from_atomic(&self, values: &[f64]) -> Vec<f64> {
+        let factor = self.energy.per_hartree() * self.length.to_bohr();
+        values.iter().map(|x| x * factor).collect()
+    }
+
+    /// Convert energies in Hartree to this unit
+    fn energies_from_hartree(&self, values: &[f64]) -> Vec<f64> {
+        let factor = self.energy.per_hartree();
+        values.iter().map(|x| x * factor).collect()
+    }
+}
+
+impl DFTD3Structure {
+    /// Create new molecular structure data, positions and lattice in given unit (failable)
+    pub fn new_with_units_f(
+        natoms: usize,
+        numbers: &[usize],
+        positions: &[f64],
+        lattice: Option<&[f64]>,
+        periodic: Option<&[bool]>,
+        unit: LengthUnit,
+    ) -> Result<Self, DFTD3Error> {
+        let units = Units {
+            length: unit,
+            ..Default::default()
+        };
+        let positions = units.length_to_bohr(positions);
+        let lattice = lattice.map(|x| units.length_to_bohr(x));
+        Self::new_f(natoms, numbers, &positions, lattice.as_deref(), periodic)
+    }
+
+    /// Create new molecular structure data, positions and lattice in given unit
+    ///
+    /// # Arguments
+    ///
+    /// * `numbers` - numbers [natoms]
+    /// * `positions` - positions [natoms][3]
+    /// * `lattice` - lattice [3][3]
+    /// * `periodic` - periodic [3]
+    /// * `unit` - unit of positions and lattice
+    pub fn new_with_units(
+        natoms: usize,
+        numbers: &[usize],
+        positions: &[f64],
+        lattice: Option<&[f64]>,
+        periodic: Option<&[bool]>,
+        unit: LengthUnit,
+    ) -> Self {
+        Self::new_with_units_f(natoms, numbers, positions, lattice, periodic, unit).unwrap()
+    }
+
+    /// Update coordinates and lattice parameters in given unit (failable)
+    pub fn update_with_units_f(
+        &self,
+        positions: &[f64],
+        lattice: Option<&[f64]>,
+        unit: LengthUnit,
+    ) -> Result<(), DFTD3Error> {
+        let units = Units {
+            length: unit,
+            ..Default::default()
+        };
+        let positions = units.length_to_bohr(positions);
+        let lattice = lattice.map(|x| units.length_to_bohr(x));
+        self.update_f(&positions, lattice.as_deref())
+    }
+
+    /// Update coordinates and lattice parameters in given unit
+    pub fn update_with_units(&self, positions: &[f64], lattice: Option<&[f64]>, unit: LengthUnit) {
+        self.update_with_units_f(positions, lattice, unit).unwrap()
+    }
+
+    /// Get positions [natoms][3] in given unit
+    pub fn get_positions_with_units(&self, unit: LengthUnit) -> Vec<f64> {
+        self.get_positions()
+            .iter()
+            .map(|x| x / unit.to_bohr())
+            .collect()
+    }
+
+    /// Get lattice [3][3] in given unit, if given
+    pub fn get_lattice_with_units(&self, unit: LengthUnit) -> Option<Vec<f64>> {
+        self.get_lattice()
+            .map(|x| x.iter().map(|v| v / unit.to_bohr()).collect())
+    }
+}
+
+impl DFTD3Model {
+    /// Set realspace cutoffs in given unit (failable)
+    pub fn set_realspace_cutoff_with_units_f(
+        &self,
+        disp2: f64,
+        disp3: f64,
+        cn: f64,
+        unit: LengthUnit,
+    ) -> Result<(), DFTD3Error> {
+        let factor = unit.to_bohr();
+        self.set_realspace_cutoff_f(disp2 * factor, disp3 * factor, cn * factor)
+    }
+
+    /// Set realspace cutoffs in given unit
+    pub fn set_realspace_cutoff_with_units(
+        &self,
+        disp2: f64,
+        disp3: f64,
+        cn: f64,
+        unit: LengthUnit,
+    ) {
+        self.set_realspace_cutoff_with_units_f(disp2, disp3, cn, unit)
+            .unwrap()
+    }
+}
+
+impl DFTD3Output {
+    /// Convert result from atomic units to given unit system
+    ///
+    /// Energy and sigma are in energy unit, and gradient is in energy/length unit.
+    pub fn in_units(&self, units: &Units) -> Self {
+        Self {
+            energy: units.energy_from_hartree(self.energy),
+            gradient: self
+                .gradient
+                .as_ref()
+                .map(|x| units.gradient_from_atomic(x)),
+            sigma: self
+                .sigma
+                .as_ref()
+                .map(|x| x.iter().map(|&v| units.energy_from_hartree(v)).collect()),
+        }
+    }
+}
+
+impl PairwiseDispersion {
+    /// Convert pairwise energies from Hartree to energy unit of given unit system
+    pub fn in_units(&self, units: &Units) -> Self {
+        Self::from_vecs(
+            self.get_natoms(),
+            units.energies_from_hartree(self.get_pair_energy2()),
+            units.energies_from_hartree(self.get_pair_energy3()),
+        )
+    }
+}
+
+impl DFTD3Interaction {
+    /// Convert result from atomic units to given unit system
+    pub fn in_units(&self, units: &Units) -> Self {
+        Self {
+            energy: units.energy_from_hartree(self.energy),
+            complex_energy: units.energy_from_hartree(self.complex_energy),
+            fragment_energies: self
+                .fragment_energies
+                .iter()
+                .map(|&x| units.energy_from_hartree(x))
+                .collect(),
+            gradient: units.gradient_from_atomic(&self.gradient),
+            fragment_gradients: self
+                .fragment_gradients
+                .iter()
+                .map(|x| units.gradient_from_atomic(x))
+                .collect(),
+            gcp_energy: self.gcp_energy.map(|x| units.energy_from_hartree(x)),
+        }
+    }
+}
+
+impl DFTD3Hessian {
+    /// Convert result from atomic units to given unit system
+    ///
+    /// Hessian is in energy/length^2 unit, and strain second derivatives are in energy unit.
+    pub fn in_units(&self, units: &Units) -> Self {
+        let factor = units.energy.per_hartree() * units.length.to_bohr().powi(2);
+        Self {
+            hessian: self.hessian.iter().map(|x| x * factor).collect(),
+            strain_hessian: self
+                .strain_hessian
+                .as_ref()
+                .map(|x| units.energies_from_hartree(x)),
+        }
+    }
+}
+
+impl DFTD3Stress {
+    /// Convert result from atomic units to given unit system
+    ///
+    /// Volume is in length^3 unit, stress is in energy/length^3 unit, and lattice gradient is in
+    /// energy/length unit.
+    pub fn in_units(&self, units: &Units) -> Self {
+        let volume_factor = units.length.to_bohr().powi(3);
+        Self {
+            volume: self.volume / volume_factor,
+            stress: units
+                .energies_from_hartree(&self.stress)
+                .iter()
+                .map(|x| x * volume_factor)
+                .collect(),
+            lattice_gradient: units.gradient_from_atomic(&self.lattice_gradient),
+        }
+    }
+}
+
+impl DerivativeCheck {
+    /// Convert result from atomic units to given unit system
+    ///
+    /// Gradient errors are in energy/length unit, and sigma errors are in energy unit.
+    pub fn in_units(&self, units: &Units) -> Self {
+        Self {
+            gradient_error: units.gradient_from_atomic(&self.gradient_error),
+            sigma_error: units.energies_from_hartree(&self.sigma_error),
+        }
+    }
+}
+
+/// Evaluate the dispersion energy and its derivatives in given unit system (failable)
+pub fn get_dispersion_with_units_f(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    eval_grad: bool,
+    eval_sigma: bool,
+    units: &Units,
+) -> Result<DFTD3Output, DFTD3Error> {
+    let output: DFTD3Output =
+        get_dispersion_f(structure, model, param, eval_grad, eval_sigma)?.into();
+    Ok(output.in_units(units))
+}
+
+/// Evaluate the dispersion energy and its derivatives in given unit system
+pub fn get_dispersion_with_units(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+    eval_grad: bool,
+    eval_sigma: bool,
+    units: &Units,
+) -> DFTD3Output {
+    get_dispersion_with_units_f(structure, model, param, eval_grad, eval_sigma, units).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_angstrom_input() {
+        let numbers = [1, 1, 8];
+        let positions_bohr = [0.0, 0.0, 0.0, 0.0, 0.0, 1.4, 1.2, 0.3, 0.7];
+        let positions_ang = positions_bohr.map(|x| x * BOHR_TO_ANGSTROM);
+        let param = DFTD3Param::load_rational_damping("B3LYP", false);
+
+        let structure = DFTD3Structure::new(3, &numbers, &positions_bohr, None, None);
+        let model = DFTD3Model::new(&structure);
+        let reference: DFTD3Output = get_dispersion(&structure, &model, &param, true, true).into();
+
+        let structure = DFTD3Structure::new_with_units(
+            3,
+            &numbers,
+            &positions_ang,
+            None,
+            None,
+            LengthUnit::Angstrom,
+        );
+        let model = DFTD3Model::new(&structure);
+        let units = Units::new(LengthUnit::Angstrom, EnergyUnit::KcalPerMol);
+        let output = get_dispersion_with_units(&structure, &model, &param, true, true, &units);
+
+        assert!((output.energy - reference.energy * HARTREE_TO_KCAL_PER_MOL).abs() < 1e-10);
+        let g_ref = reference.gradient.unwrap()[5] * HARTREE_TO_KCAL_PER_MOL / BOHR_TO_ANGSTROM;
+        assert!((output.gradient.unwrap()[5] - g_ref).abs() < 1e-10);
+
+        // positions read back in input unit
+        let positions = structure.get_positions_with_units(LengthUnit::Angstrom);
+        assert!(positions
+            .iter()
+            .zip(positions_ang)
+            .all(|(a, b)| (a - b).abs() < 1e-12));
+    }
+
+    #[test]
+    fn test_result_units() {
+        let units = Units::ANGSTROM_EV;
+        let hessian = DFTD3Hessian {
+            hessian: vec![1.0],
+            strain_hessian: Some(vec![1.0]),
+        }
+        .in_units(&units);
+        let factor = HARTREE_TO_EV / BOHR_TO_ANGSTROM.powi(2);
+        assert!((hessian.hessian[0] - factor).abs() < 1e-10);
+        assert!((hessian.strain_hessian.unwrap()[0] - HARTREE_TO_EV).abs() < 1e-10);
+
+        let stress = DFTD3Stress {
+            volume: 1.0,
+            stress: vec![1.0; 9],
+            lattice_gradient: vec![1.0; 9],
+        }
+        .in_units(&units);
+        assert!((stress.volume - BOHR_TO_ANGSTROM.powi(3)).abs() < 1e-12);
+        assert!((stress.stress[0] - HARTREE_PER_BOHR3_TO_EV_PER_ANGSTROM3).abs() < 1e-10);
+        assert!((stress.lattice_gradient[0] - HARTREE_TO_EV / BOHR_TO_ANGSTROM).abs() < 1e-10);
+
+        let pairwise = PairwiseDispersion::from_vecs(1, vec![1.0], vec![2.0]).in_units(&units);
+        assert!((pairwise.get_pair_energy2()[0] - HARTREE_TO_EV).abs() < 1e-10);
+        assert!((pairwise.get_pair_energy3()[0] - 2.0 * HARTREE_TO_EV).abs() < 1e-10);
+    }
+}
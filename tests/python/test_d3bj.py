@@ -0,0 +1,84 @@
+# Python counterpart of tests/test_d3bj.rs, exercising the same Rust code path through PyO3.
+
+import numpy as np
+import pytest
+
+from rest_dftd3 import (
+    DFTD3Exception,
+    DFTD3Model,
+    DFTD3Param,
+    DFTD3Structure,
+    get_dispersion,
+    get_pairwise_dispersion,
+)
+
+# fmt: off
+COORDS = np.array([
+    [-0.358732711996, -1.219622503921,  0.131917549659],
+    [ 2.119585463724, -0.669086968776,  0.829772153711],
+    [ 2.809117479597,  1.818998649482,  1.345687626574],
+    [ 1.038082099274,  3.759060826791,  1.167603107711],
+    [-1.431707789689,  3.178259749841,  0.466491948077],
+    [-2.150868246993,  0.7002414472  , -0.054012766253],
+    [-3.85213636554 ,  5.802328175966,  0.217631119828],
+    [ 3.501540139604, -2.166447600808,  0.971544490939],
+    [ 1.559116125892,  5.692189547927,  1.567311393773],
+    [ 4.728585655927,  2.260931747079,  1.890325797025],
+    [-4.076404165723,  0.286497902539, -0.591827207478],
+    [-0.90861903551 , -3.146363064474, -0.270076664397],
+    [-7.856429540441, 10.827345280117, -1.660617862724],
+    [-9.115644677165,  9.063295559651, -2.954460822835],
+    [-5.344785190358, 11.282785671401, -3.951775457912],
+    [-4.096127437596, 12.703412959362, -3.153238235825],
+    [-4.280144282975,  9.55225545373 , -4.233361627772],
+    [-6.082130593652, 11.976040508211, -5.735483303832],
+])
+# fmt: on
+CHARGES = [6, 6, 6, 6, 6, 6, 35, 1, 1, 1, 1, 1, 16, 1, 6, 1, 1, 1]
+
+
+@pytest.fixture
+def structure():
+    return DFTD3Structure(CHARGES, COORDS)
+
+
+def test_d3bj(structure):
+    model = DFTD3Model(structure)
+
+    # PW6B95, d3bj
+    param = DFTD3Param.load_rational_damping("PW6B95", False)
+    energy, grad, sigma = get_dispersion(structure, model, param, True, True)
+    assert energy == pytest.approx(-0.01009386, abs=1e-7)
+    assert grad.shape == (18, 3)
+    assert sigma.shape == (3, 3)
+
+    # PW6B95, d3zero
+    param = DFTD3Param.load_zero_damping("PW6B95", False)
+    energy, _, _ = get_dispersion(structure, model, param, True, True)
+    assert energy == pytest.approx(-0.00574098, abs=1e-7)
+
+    # PW6B95, d3zero, atm
+    param = DFTD3Param.load_zero_damping("PW6B95", True)
+    energy, _, _ = get_dispersion(structure, model, param, True, True)
+    assert energy == pytest.approx(-0.00574289, abs=1e-7)
+
+
+def test_pairwise(structure):
+    model = DFTD3Model(structure)
+    param = DFTD3Param.load_rational_damping("PW6B95", True)
+    energy, grad, sigma = get_dispersion(structure, model, param, False, False)
+    assert grad is None and sigma is None
+
+    pair2, pair3 = get_pairwise_dispersion(structure, model, param)
+    assert pair2.shape == (18, 18)
+    assert pair2.sum() + pair3.sum() == pytest.approx(energy, abs=1e-10)
+
+
+def test_errors(structure):
+    with pytest.raises(DFTD3Exception):
+        DFTD3Param.load_rational_damping("not-a-functional", False)
+    with pytest.raises(DFTD3Exception):
+        DFTD3Structure(CHARGES, COORDS[:, :2])
+    model = DFTD3Model(structure)
+    with pytest.raises(DFTD3Exception):
+        model.set_realspace_cutoff(40.0, 60.0, 40.0)
@@ -0,0 +1,71 @@
+use rest_dftd3::prelude::*;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Damping parameters of all kinds, with and without three-body contribution.
+    fn all_damping_params() -> Vec<(String, DFTD3Param)> {
+        let mut params = vec![];
+        for (s9, atm) in [(0.0, ""), (1.0, ", atm")] {
+            #[rustfmt::skip]
+            let kinds = [
+                ("zero", DFTD3Param::new_zero_damping(1.0, 1.703, s9, 1.261, 1.0, 14.0)),
+                ("rational", DFTD3Param::new_rational_damping(1.0, 1.9889, s9, 0.3981, 4.4211, 14.0)),
+                ("mzero", DFTD3Param::new_mzero_damping(1.0, 1.532981, s9, 1.338153, 1.0, 14.0, 0.013988)),
+                ("mrational", DFTD3Param::new_mrational_damping(1.0, 1.466677, s9, 0.278672, 4.606311, 14.0)),
+                ("optimizedpower", DFTD3Param::new_optimizedpower_damping(1.0, 0.9, s9, 0.3, 4.5, 14.0, 6.0)),
+            ];
+            for (name, param) in kinds {
+                params.push((format!("{}{}", name, atm), param));
+            }
+        }
+        params
+    }
+
+    fn assert_consistent(structure: &DFTD3Structure, tag: &str) {
+        let model = DFTD3Model::new(structure);
+        for (name, param) in all_damping_params() {
+            let check = check_derivatives(structure, &model, &param);
+            println!(
+                "{:10} {:20} gradient error {:.3e}, sigma error {:.3e}",
+                tag,
+                name,
+                check.max_gradient_error(),
+                check.max_sigma_error()
+            );
+            assert!(check.max_gradient_error() < 1e-7, "{} {}", tag, name);
+            assert!(check.max_sigma_error() < 1e-5, "{} {}", tag, name);
+        }
+    }
+
+    #[test]
+    fn test_derivatives_molecule() {
+        // water dimer
+        #[rustfmt::skip]
+        let positions = [
+            0.000, 0.000, 0.000,   1.810, 0.000, 0.000,  -0.450, 1.750, 0.000,
+            5.600, 0.000, 0.000,   6.200, 1.700, 0.000,   6.200,-1.700, 0.300,
+        ];
+        let numbers = [8, 1, 1, 8, 1, 1];
+        let structure = DFTD3Structure::new(6, &numbers, &positions, None, None);
+        assert_consistent(&structure, "molecule");
+    }
+
+    #[test]
+    fn test_derivatives_periodic() {
+        // rock salt, primitive fcc cell
+        let a = 5.3;
+        #[rustfmt::skip]
+        let lattice = [
+            0.0, a, a,
+            a, 0.0, a,
+            a, a, 0.0,
+        ];
+        let positions = [0.0, 0.0, 0.0, a, a, a];
+        let periodic = [true, true, true];
+        let structure =
+            DFTD3Structure::new(2, &[11, 17], &positions, Some(&lattice), Some(&periodic));
+        assert_consistent(&structure, "periodic");
+    }
+}
@@ -0,0 +1,255 @@
+use proptest::prelude::*;
+use rest_dftd3::prelude::*;
+
+/// Elements of random molecules and cells
+const ELEMENTS: &[usize] = &[1, 6, 7, 8, 9, 14, 16, 17, 35];
+
+/// Tolerance of energies (in Hartree) and gradients
+const TOL: f64 = 1e-9;
+
+/// Relative tolerance of energies and gradients of periodic cells, with respect to the largest
+/// absolute value
+const REL_TOL: f64 = 1e-7;
+
+/// Random molecule: atomic numbers and positions [natoms][3] (in Bohr) on a jittered grid
+fn molecule() -> impl Strategy<Value = (Vec<usize>, Vec<f64>)> {
+    (2..=6usize).prop_flat_map(|natoms| {
+        (
+            prop::collection::vec(prop::sample::select(ELEMENTS), natoms),
+            prop::collection::vec(-0.8..0.8f64, 3 * natoms),
+        )
+            .prop_map(move |(numbers, jitter)| {
+                let positions = (0..3 * natoms)
+                    .map(|ik| {
+                        let (i, k) = (ik / 3, ik % 3);
+                        let grid = [i % 2, (i / 2) % 2, i / 4][k];
+                        4.0 * grid as f64 + jitter[ik]
+                    })
+                    .collect();
+                (numbers, positions)
+            })
+    })
+}
+
+/// Random periodic cell: atomic numbers, positions and lattice [3][3] (in Bohr)
+fn cell() -> impl Strategy<Value = (Vec<usize>, Vec<f64>, Vec<f64>)> {
+    (
+        prop::collection::vec(prop::sample::select(ELEMENTS), 1..=3),
+        prop::collection::vec(8.0..11.0f64, 3),
+        prop::collection::vec(-1.0..1.0f64, 3),
+        prop::collection::vec(0.0..1.0f64, 9),
+    )
+        .prop_map(|(numbers, lengths, shear, fractional)| {
+            #[rustfmt::skip]
+            let lattice = vec![
+                lengths[0], 0.0, 0.0,
+                shear[0], lengths[1], 0.0,
+                shear[1], shear[2], lengths[2],
+            ];
+            let positions = (0..numbers.len())
+                .flat_map(|i| {
+                    let f = &fractional[3 * i..3 * i + 3];
+                    let lattice = &lattice;
+                    (0..3).map(move |k| (0..3).map(|l| f[l] * lattice[3 * l + k]).sum::<f64>())
+                })
+                .collect();
+            (numbers, positions, lattice)
+        })
+}
+
+/// Rotation matrix (row-major) from Euler angles
+fn rotation(angles: &[f64]) -> [f64; 9] {
+    let (sa, ca) = angles[0].sin_cos();
+    let (sb, cb) = angles[1].sin_cos();
+    let (sc, cc) = angles[2].sin_cos();
+    let rz1 = [ca, -sa, 0.0, sa, ca, 0.0, 0.0, 0.0, 1.0];
+    let ry = [cb, 0.0, sb, 0.0, 1.0, 0.0, -sb, 0.0, cb];
+    let rz2 = [cc, -sc, 0.0, sc, cc, 0.0, 0.0, 0.0, 1.0];
+    matmul(&matmul(&rz1, &ry), &rz2)
+}
+
+fn matmul(a: &[f64], b: &[f64]) -> [f64; 9] {
+    let mut c = [0.0; 9];
+    for i in 0..3 {
+        for j in 0..3 {
+            c[3 * i + j] = (0..3).map(|k| a[3 * i + k] * b[3 * k + j]).sum();
+        }
+    }
+    c
+}
+
+/// Apply 3x3 matrix to each 3-vector of `vectors`
+fn transform(m: &[f64], vectors: &[f64]) -> Vec<f64> {
+    vectors
+        .chunks(3)
+        .flat_map(|v| (0..3).map(move |a| (0..3).map(|b| m[3 * a + b] * v[b]).sum::<f64>()))
+        .collect()
+}
+
+/// Fractional coordinates of each 3-vector of `vectors` in lattice [3][3] (rows are lattice vectors)
+fn fractional(lattice: &[f64], vectors: &[f64]) -> Vec<f64> {
+    let l = |i: usize, k: usize| lattice[3 * i + k];
+    // inverse of lattice by cofactors; r = f L, so f = r L^-1
+    let cofactor = |i: usize, k: usize| {
+        let (i1, i2, k1, k2) = ((i + 1) % 3, (i + 2) % 3, (k + 1) % 3, (k + 2) % 3);
+        l(i1, k1) * l(i2, k2) - l(i1, k2) * l(i2, k1)
+    };
+    let det = (0..3).map(|k| l(0, k) * cofactor(0, k)).sum::<f64>();
+    let inverse = (0..9)
+        .map(|kj| cofactor(kj % 3, kj / 3) / det)
+        .collect::<Vec<f64>>();
+    vectors
+        .chunks(3)
+        .flat_map(|r| {
+            let inverse = &inverse;
+            (0..3).map(move |j| (0..3).map(|k| r[k] * inverse[3 * k + j]).sum::<f64>())
+        })
+        .collect()
+}
+
+/// Wrap positions [natoms][3] into the cell, i.e. fractional coordinates in [0, 1)
+fn wrap(lattice: &[f64], positions: &[f64]) -> Vec<f64> {
+    let wrapped = fractional(lattice, positions)
+        .into_iter()
+        .map(|f| f - f.floor())
+        .collect::<Vec<f64>>();
+    wrapped
+        .chunks(3)
+        .flat_map(|f| (0..3).map(move |k| (0..3).map(|l| f[l] * lattice[3 * l + k]).sum::<f64>()))
+        .collect()
+}
+
+fn max_deviation(a: &[f64], b: &[f64]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y).abs())
+        .fold(0.0, f64::max)
+}
+
+fn evaluate(
+    structure: &DFTD3Structure,
+    model: &DFTD3Model,
+    param: &DFTD3Param,
+) -> Result<(f64, Vec<f64>), DFTD3Error> {
+    let (energy, gradient, _) = get_dispersion_f(structure, model, param, true, false)?;
+    Ok((energy, gradient.unwrap()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(24))]
+
+        #[test]
+        fn test_rigid_motion(
+            (numbers, positions) in molecule(),
+            translation in prop::collection::vec(-20.0..20.0f64, 3),
+            angles in prop::collection::vec(0.0..std::f64::consts::TAU, 3),
+        ) {
+            let natoms = numbers.len();
+            let structure = DFTD3Structure::new_f(natoms, &numbers, &positions, None, None)?;
+            let model = DFTD3Model::new_f(&structure)?;
+            let param = DFTD3Param::load_rational_damping_f("PBE0", true)?;
+            let (energy, gradient) = evaluate(&structure, &model, &param)?;
+
+            // gradient sums to zero
+            for k in 0..3 {
+                let sum = gradient.iter().skip(k).step_by(3).sum::<f64>();
+                prop_assert!(sum.abs() < TOL);
+            }
+
+            // translation
+            let translated = positions
+                .iter()
+                .enumerate()
+                .map(|(i, x)| x + translation[i % 3])
+                .collect::<Vec<f64>>();
+            structure.update_f(&translated, None)?;
+            let (energy_t, gradient_t) = evaluate(&structure, &model, &param)?;
+            prop_assert!((energy_t - energy).abs() < TOL);
+            prop_assert!(max_deviation(&gradient_t, &gradient) < TOL);
+
+            // rotation; gradient rotates with positions
+            let r = rotation(&angles);
+            structure.update_f(&transform(&r, &positions), None)?;
+            let (energy_r, gradient_r) = evaluate(&structure, &model, &param)?;
+            prop_assert!((energy_r - energy).abs() < TOL);
+            prop_assert!(max_deviation(&gradient_r, &transform(&r, &gradient)) < TOL);
+        }
+
+        #[test]
+        fn test_permutation(
+            (numbers, positions) in molecule(),
+            seed in any::<u64>(),
+        ) {
+            let natoms = numbers.len();
+            let structure = DFTD3Structure::new_f(natoms, &numbers, &positions, None, None)?;
+            let model = DFTD3Model::new_f(&structure)?;
+            let param = DFTD3Param::load_zero_damping_f("PBE0", true)?;
+            let (energy, gradient) = evaluate(&structure, &model, &param)?;
+
+            // permutation by sorting with pseudo-random keys
+            let mut order = (0..natoms).collect::<Vec<usize>>();
+            order.sort_by_key(|&i| (i as u64 + 1).wrapping_mul(seed | 1).rotate_left(17));
+            let numbers_p = order.iter().map(|&i| numbers[i]).collect::<Vec<_>>();
+            let positions_p = order
+                .iter()
+                .flat_map(|&i| positions[3 * i..3 * i + 3].to_vec())
+                .collect::<Vec<_>>();
+            let structure = DFTD3Structure::new_f(natoms, &numbers_p, &positions_p, None, None)?;
+            let model = DFTD3Model::new_f(&structure)?;
+            let (energy_p, gradient_p) = evaluate(&structure, &model, &param)?;
+            prop_assert!((energy_p - energy).abs() < TOL);
+            for (ip, &i) in order.iter().enumerate() {
+                let deviation = max_deviation(&gradient_p[3 * ip..3 * ip + 3], &gradient[3 * i..3 * i + 3]);
+                prop_assert!(deviation < TOL);
+            }
+        }
+
+        #[test]
+        fn test_periodic_cell_choice(
+            (numbers, positions, lattice) in cell(),
+            shifts in prop::collection::vec(-1i32..=1, 9),
+            combination in prop::sample::select(vec![(0usize, 1usize), (0, 2), (1, 0), (1, 2), (2, 0), (2, 1)]),
+            multiple in prop_oneof![Just(-1i32), Just(1)],
+        ) {
+            let natoms = numbers.len();
+            let periodic = [true; 3];
+
+            // atoms moved out of the cell by at most one lattice vector
+            let outside = (0..3 * natoms)
+                .map(|ik| {
+                    let (i, k) = (ik / 3, ik % 3);
+                    let shift = (0..3).map(|l| shifts[3 * i + l] as f64 * lattice[3 * l + k]).sum::<f64>();
+                    positions[ik] + shift
+                })
+                .collect::<Vec<f64>>();
+            let structure =
+                DFTD3Structure::new_f(natoms, &numbers, &outside, Some(&lattice), Some(&periodic))?;
+            let model = DFTD3Model::new_f(&structure)?;
+            let param = DFTD3Param::load_rational_damping_f("PBE0", false)?;
+            let (energy_o, gradient_o) = evaluate(&structure, &model, &param)?;
+
+            // wrapping atoms into the cell
+            structure.update_f(&wrap(&lattice, &outside), None)?;
+            let (energy, gradient) = evaluate(&structure, &model, &param)?;
+            let energy_tol = REL_TOL * energy.abs();
+            let gradient_tol = REL_TOL * gradient.iter().map(|x| x.abs()).fold(0.0, f64::max);
+            prop_assert!((energy_o - energy).abs() <= energy_tol);
+            prop_assert!(max_deviation(&gradient_o, &gradient) <= gradient_tol);
+
+            // lattice-equivalent cell by unimodular transformation a_i' = a_i + n a_j
+            let (i, j) = combination;
+            let mut equivalent = lattice.clone();
+            for k in 0..3 {
+                equivalent[3 * i + k] += multiple as f64 * lattice[3 * j + k];
+            }
+            structure.update_f(&wrap(&lattice, &outside), Some(&equivalent))?;
+            let (energy_e, gradient_e) = evaluate(&structure, &model, &param)?;
+            prop_assert!((energy_e - energy).abs() <= energy_tol);
+            prop_assert!(max_deviation(&gradient_e, &gradient) <= gradient_tol);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use rest_dftd3::prelude::*;
+use rest_dftd3::server::*;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Read, Write};
+
+    // mock i-PI server side of the protocol
+
+    fn send_header(stream: &mut impl Write, header: &str) {
+        stream
+            .write_all(format!("{:<12}", header).as_bytes())
+            .unwrap();
+    }
+
+    fn recv_header(stream: &mut impl Read) -> String {
+        let mut buffer = [0u8; 12];
+        stream.read_exact(&mut buffer).unwrap();
+        String::from_utf8_lossy(&buffer).trim().to_string()
+    }
+
+    fn send_f64s(stream: &mut impl Write, values: &[f64]) {
+        for x in values {
+            stream.write_all(&x.to_ne_bytes()).unwrap();
+        }
+    }
+
+    fn recv_f64s(stream: &mut impl Read, n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|_| {
+                let mut buffer = [0u8; 8];
+                stream.read_exact(&mut buffer).unwrap();
+                f64::from_ne_bytes(buffer)
+            })
+            .collect()
+    }
+
+    fn recv_i32(stream: &mut impl Read) -> i32 {
+        let mut buffer = [0u8; 4];
+        stream.read_exact(&mut buffer).unwrap();
+        i32::from_ne_bytes(buffer)
+    }
+
+    /// One step of i-PI server: send positions and cell (columns are lattice vectors), and receive
+    /// energy, forces and virial.
+    fn step(stream: &mut (impl Read + Write), positions: &[f64], cell: &[f64]) -> IpiResult {
+        send_header(stream, "STATUS");
+        assert_eq!(recv_header(stream), "READY");
+        send_header(stream, "POSDATA");
+        send_f64s(stream, cell);
+        send_f64s(stream, &[0.0; 9]);
+        stream
+            .write_all(&((positions.len() / 3) as i32).to_ne_bytes())
+            .unwrap();
+        send_f64s(stream, positions);
+        send_header(stream, "STATUS");
+        assert_eq!(recv_header(stream), "HAVEDATA");
+        send_header(stream, "GETFORCE");
+        assert_eq!(recv_header(stream), "FORCEREADY");
+        let energy = recv_f64s(stream, 1)[0];
+        let natoms = recv_i32(stream) as usize;
+        let forces = recv_f64s(stream, 3 * natoms);
+        let virial = recv_f64s(stream, 9);
+        let nextra = recv_i32(stream) as usize;
+        let mut extra = vec![0u8; nextra];
+        stream.read_exact(&mut extra).unwrap();
+        IpiResult {
+            energy,
+            forces,
+            virial,
+        }
+    }
+
+    #[test]
+    fn test_ipi_tcp() {
+        let numbers = [11, 17];
+        let a = 7.6;
+        let cell = [a, 0.0, 0.0, 0.0, a, 0.0, 0.0, 0.0, a];
+        let frames = [
+            [0.0, 0.0, 0.0, 3.8, 3.8, 3.8],
+            [0.1, 0.0, -0.1, 3.7, 3.9, 3.8],
+        ];
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = IpiAddress::Tcp(listener.local_addr().unwrap().to_string());
+        let client = std::thread::spawn(move || {
+            let param = DFTD3Param::load_rational_damping("PBE", false);
+            let mut driver = IpiDriver::new(&numbers, param, true);
+            driver.connect(&address)
+        });
+        let (mut stream, _) = listener.accept().unwrap();
+        let results = frames
+            .iter()
+            .map(|positions| step(&mut stream, positions, &cell))
+            .collect::<Vec<_>>();
+        send_header(&mut stream, "EXIT");
+        assert_eq!(client.join().unwrap(), 2);
+
+        // reference by direct evaluation
+        let param = DFTD3Param::load_rational_damping("PBE", false);
+        let periodic = [true, true, true];
+        for (positions, result) in frames.iter().zip(results) {
+            let structure =
+                DFTD3Structure::new(2, &numbers, positions, Some(&cell), Some(&periodic));
+            let model = DFTD3Model::new(&structure);
+            let (energy, gradient, sigma) = get_dispersion(&structure, &model, &param, true, true);
+            assert!((result.energy - energy).abs() < 1e-12);
+            for (f, g) in result.forces.iter().zip(gradient.unwrap()) {
+                assert!((f + g).abs() < 1e-12);
+            }
+            // virial is sent transposed
+            let sigma = sigma.unwrap();
+            for a in 0..3 {
+                for b in 0..3 {
+                    assert!((result.virial[3 * b + a] + sigma[3 * a + b]).abs() < 1e-12);
+                }
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ipi_unix() {
+        let numbers = [8, 1, 1];
+        let positions = [0.0, 0.0, 0.0, 1.81, 0.0, 0.0, -0.45, 1.75, 0.0];
+        let path = std::env::temp_dir().join(format!("ipi_rest_dftd3_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let address = IpiAddress::Unix(path.to_string_lossy().to_string());
+        let client = std::thread::spawn(move || {
+            let param = DFTD3Param::load_zero_damping("B3LYP", false);
+            let mut driver = IpiDriver::new(&numbers, param, false);
+            driver.connect(&address)
+        });
+        let (mut stream, _) = listener.accept().unwrap();
+        let result = step(&mut stream, &positions, &[0.0; 9]);
+        send_header(&mut stream, "EXIT");
+        assert_eq!(client.join().unwrap(), 1);
+        std::fs::remove_file(&path).unwrap();
+
+        let param = DFTD3Param::load_zero_damping("B3LYP", false);
+        let structure = DFTD3Structure::new(3, &numbers, &positions, None, None);
+        let model = DFTD3Model::new(&structure);
+        let (energy, _, _) = get_dispersion(&structure, &model, &param, false, false);
+        assert!((result.energy - energy).abs() < 1e-12);
+    }
+}
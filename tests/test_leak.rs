@@ -0,0 +1,61 @@
+use rest_dftd3::prelude::*;
+
+/// Resident set size (in bytes) of this process, from `/proc/self/statm`
+#[cfg(target_os = "linux")]
+fn resident_size() -> usize {
+    let statm = std::fs::read_to_string("/proc/self/statm").unwrap();
+    let pages = statm
+        .split_whitespace()
+        .nth(1)
+        .unwrap()
+        .parse::<usize>()
+        .unwrap();
+    pages * 4096
+}
+
+/// Load parameters of all damping kinds and gCP, valid and invalid names
+fn load_parameters(structure: &DFTD3Structure, long_name: &str) {
+    for damping in ["d3zero", "d3bj", "d3zerom", "d3bjm", "d3op"] {
+        let param = DFTD3Param::load_damping_f(damping, "b3lyp", true);
+        assert!(param.is_ok());
+        let param = DFTD3Param::load_damping_f(damping, long_name, false);
+        assert!(param.is_err());
+    }
+    let gcp = DFTD3GCP::load_gcp_param_f(structure, "hf3c", "minix");
+    assert!(gcp.is_ok());
+    let gcp = DFTD3GCP::load_gcp_param_f(structure, long_name, long_name);
+    assert!(gcp.is_err());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Loading parameters many times must not grow memory; with the former `into_raw` loaders
+    /// each iteration leaked the method names (here 7 x 4 KiB for invalid names).
+    ///
+    /// This test is kept in its own file so that no other test runs in the process. It is also
+    /// suitable for `valgrind --leak-check=full`.
+    #[test]
+    fn test_loader_leak() {
+        let structure =
+            DFTD3Structure::new(2, &[1, 1], &[0.0, 0.0, 0.0, 0.0, 0.0, 1.4], None, None);
+        let long_name = "x".repeat(4096);
+
+        // warm up allocator and library storage
+        for _ in 0..100 {
+            load_parameters(&structure, &long_name);
+        }
+        #[cfg(target_os = "linux")]
+        let initial = resident_size();
+        for _ in 0..2000 {
+            load_parameters(&structure, &long_name);
+        }
+        #[cfg(target_os = "linux")]
+        {
+            let growth = resident_size().saturating_sub(initial);
+            // a leak of the names alone would be about 2000 x 7 x 4 KiB = 56 MiB
+            assert!(growth < 8 * 1024 * 1024, "memory grew by {} bytes", growth);
+        }
+    }
+}
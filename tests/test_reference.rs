@@ -0,0 +1,329 @@
+use rest_dftd3::prelude::*;
+use std::collections::BTreeMap;
+
+/// Reference data: `<case> <energy> <gradient...> [sigma...]`, one case per line.
+///
+/// Entries are generated against a trusted s-dftd3 release with
+/// `DFTD3_BLESS=1 cargo test --test test_reference`, which records the library version in the header.
+/// Every case must have an entry.
+const REFERENCE_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/reference/dftd3.txt");
+
+/// Published energies: `<case> <energy>`, rounded to 1e-8 Hartree.
+///
+/// These are independent of the installed library; they are always checked, also before blessing,
+/// and never rewritten.
+const PUBLISHED_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/reference/published.txt");
+
+/// Tolerance of energies (in Hartree) and derivatives
+const ENERGY_TOL: f64 = 1e-10;
+const GRADIENT_TOL: f64 = 1e-9;
+
+/// Tolerance of published energies
+const ROUNDED_ENERGY_TOL: f64 = 1e-8;
+
+/// Test system with positions and lattice in Angstrom
+struct System {
+    name: &'static str,
+    symbols: &'static [&'static str],
+    positions: &'static [[f64; 3]],
+    lattice: Option<[[f64; 3]; 3]>,
+}
+
+impl System {
+    fn structure(&self) -> DFTD3Structure {
+        let numbers = self
+            .symbols
+            .iter()
+            .map(|x| element_number(x).unwrap())
+            .collect::<Vec<usize>>();
+        let to_bohr = |x: &[[f64; 3]]| {
+            x.iter()
+                .flatten()
+                .map(|x| x / BOHR_TO_ANGSTROM)
+                .collect::<Vec<f64>>()
+        };
+        let positions = to_bohr(self.positions);
+        let lattice = self.lattice.map(|x| to_bohr(&x));
+        let periodic = self.lattice.map(|_| [true; 3]);
+        DFTD3Structure::new(
+            numbers.len(),
+            &numbers,
+            &positions,
+            lattice.as_deref(),
+            periodic.as_ref().map(|x| &x[..]),
+        )
+    }
+}
+
+#[rustfmt::skip]
+const SYSTEMS: &[System] = &[
+    // S22 water dimer
+    System {
+        name: "water_dimer",
+        symbols: &["O", "H", "H", "O", "H", "H"],
+        positions: &[
+            [-1.551007, -0.114520,  0.000000], [-1.934259,  0.762503,  0.000000],
+            [-0.599677,  0.040712,  0.000000], [ 1.350625,  0.111469,  0.000000],
+            [ 1.680398, -0.373741, -0.758561], [ 1.680398, -0.373741,  0.758561],
+        ],
+        lattice: None,
+    },
+    // S22 methane dimer
+    System {
+        name: "methane_dimer",
+        symbols: &["C", "H", "H", "H", "H", "C", "H", "H", "H", "H"],
+        positions: &[
+            [ 0.000000, -0.000140,  1.859161], [-0.888551,  0.513060,  1.494685],
+            [ 0.888551,  0.513060,  1.494685], [ 0.000000, -1.026339,  1.494868],
+            [ 0.000000,  0.000089,  2.948284], [ 0.000000,  0.000140, -1.859161],
+            [ 0.000000, -0.000089, -2.948284], [-0.888551, -0.513060, -1.494685],
+            [ 0.888551, -0.513060, -1.494685], [ 0.000000,  1.026339, -1.494868],
+        ],
+        lattice: None,
+    },
+    // S22-style ammonia dimer
+    System {
+        name: "ammonia_dimer",
+        symbols: &["N", "H", "H", "H", "N", "H", "H", "H"],
+        positions: &[
+            [-1.578718, -0.046611,  0.000000], [-2.158621,  0.136396, -0.809565],
+            [-2.158621,  0.136396,  0.809565], [-0.849471,  0.658193,  0.000000],
+            [ 1.578718,  0.046611,  0.000000], [ 2.158621, -0.136396, -0.809565],
+            [ 0.849471, -0.658193,  0.000000], [ 2.158621, -0.136396,  0.809565],
+        ],
+        lattice: None,
+    },
+    // bromobenzene with methanethiol, as in test_d3bj
+    System {
+        name: "bromobenzene_methanethiol",
+        symbols: &["C", "C", "C", "C", "C", "C", "Br", "H", "H", "H", "H", "H", "S", "H", "C", "H", "H", "H"],
+        positions: &[
+            [-0.189833176, -0.645396435,  0.069807761], [ 1.121636324, -0.354065576,  0.439096514],
+            [ 1.486520953,  0.962572632,  0.712107225], [ 0.549329390,  1.989209324,  0.617868956],
+            [-0.757627135,  1.681862630,  0.246856908], [-1.138190460,  0.370551816, -0.028582325],
+            [-2.038462778,  3.070459841,  0.115165429], [ 1.852935245, -1.146434699,  0.514119204],
+            [ 0.825048723,  3.012176989,  0.829385472], [ 2.502259769,  1.196433556,  1.000317333],
+            [-2.157140187,  0.151608161, -0.313181471], [-0.480820487, -1.664983631, -0.142918416],
+            [-4.157443472,  5.729584377, -0.878761129], [-4.823791426,  4.796089466, -1.563433338],
+            [-2.828338520,  5.970593053, -2.091189515], [-2.167577293,  6.722356639, -1.668621815],
+            [-2.264954814,  5.054835899, -2.240198499], [-3.218524904,  6.337447714, -3.035087058],
+        ],
+        lattice: None,
+    },
+    // single atoms
+    System {
+        name: "argon_atom",
+        symbols: &["Ar"],
+        positions: &[[0.0, 0.0, 0.0]],
+        lattice: None,
+    },
+    System {
+        name: "gold_atom",
+        symbols: &["Au"],
+        positions: &[[0.0, 0.0, 0.0]],
+        lattice: None,
+    },
+    // elements across the periodic table on a 3 Angstrom grid
+    System {
+        name: "periodic_table_cluster",
+        symbols: &["H", "Li", "B", "N", "F", "Na", "Si", "Cl", "K", "Fe", "Zn", "Br", "Ag", "I", "Au", "Pb", "Rn", "U"],
+        positions: &[
+            [0.0, 0.0, 0.0], [3.0, 0.0, 0.0], [6.0, 0.0, 0.0],
+            [0.0, 3.0, 0.0], [3.0, 3.0, 0.0], [6.0, 3.0, 0.0],
+            [0.0, 6.0, 0.0], [3.0, 6.0, 0.0], [6.0, 6.0, 0.0],
+            [0.0, 0.0, 3.0], [3.0, 0.0, 3.0], [6.0, 0.0, 3.0],
+            [0.0, 3.0, 3.0], [3.0, 3.0, 3.0], [6.0, 3.0, 3.0],
+            [0.0, 6.0, 3.0], [3.0, 6.0, 3.0], [6.0, 6.0, 3.0],
+        ],
+        lattice: None,
+    },
+    // rock-salt NaCl, primitive cell
+    System {
+        name: "nacl_crystal",
+        symbols: &["Na", "Cl"],
+        positions: &[[0.0, 0.0, 0.0], [2.82, 2.82, 2.82]],
+        lattice: Some([[0.0, 2.82, 2.82], [2.82, 0.0, 2.82], [2.82, 2.82, 0.0]]),
+    },
+];
+
+/// Damping kinds and methods with parameters in s-dftd3; modified zero, modified rational and
+/// optimized power damping are only parametrized for a small set of functionals.
+const DAMPINGS: &[(&str, &[&str])] = &[
+    ("d3zero", &["PBE0", "PW6B95"]),
+    ("d3bj", &["PBE0", "PW6B95"]),
+    ("d3zerom", &["PBE0"]),
+    ("d3bjm", &["PBE0"]),
+    ("d3op", &["PBE0"]),
+];
+
+/// gCP method and basis of counterpoise cases
+const GCP_METHODS: &[(&str, &str)] = &[("hf3c", "minix"), ("pbeh3c", "def2msvp")];
+
+fn flatten(values: &[f64]) -> String {
+    values
+        .iter()
+        .map(|x| format!("{:.16e}", x))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Evaluate all cases, keyed by `<system>/<method>/<damping>[/atm]` or `<system>/gcp/<method>`
+/// (lowercase); cases failing in the library are reported as errors
+fn evaluate_cases() -> BTreeMap<String, Result<Vec<f64>, String>> {
+    let mut cases = BTreeMap::new();
+    for system in SYSTEMS {
+        let structure = system.structure();
+        let model = DFTD3Model::new(&structure);
+        let periodic = system.lattice.is_some();
+        for (damping, methods) in DAMPINGS {
+            for method in methods.iter() {
+                for atm in [false, true] {
+                    let values = DFTD3Param::load_damping_f(damping, method, atm)
+                        .and_then(|param| {
+                            get_dispersion_f(&structure, &model, &param, true, periodic)
+                        })
+                        .map(|(energy, gradient, sigma)| {
+                            let mut values = vec![energy];
+                            values.extend(gradient.unwrap());
+                            values.extend(sigma.unwrap_or_default());
+                            values
+                        })
+                        .map_err(|err| err.get_message());
+                    let atm = if atm { "/atm" } else { "" };
+                    let method = method.to_lowercase();
+                    cases.insert(
+                        format!("{}/{}/{}{}", system.name, method, damping, atm),
+                        values,
+                    );
+                }
+            }
+        }
+        for (method, basis) in GCP_METHODS {
+            let values = DFTD3GCP::load_gcp_param_f(&structure, method, basis)
+                .and_then(|gcp| get_counterpoise_f(&structure, &gcp))
+                .map(|(energy, gradient, sigma)| {
+                    let mut values = vec![energy];
+                    values.extend(gradient);
+                    if periodic {
+                        values.extend(sigma);
+                    }
+                    values
+                })
+                .map_err(|err| err.get_message());
+            cases.insert(format!("{}/gcp/{}", system.name, method), values);
+        }
+    }
+    cases
+}
+
+fn read_entries(path: &str) -> BTreeMap<String, Vec<f64>> {
+    let content = std::fs::read_to_string(path).unwrap_or_else(|err| {
+        panic!(
+            "Cannot read {} ({}); generate it with DFTD3_BLESS=1 against a trusted s-dftd3 release",
+            path, err
+        )
+    });
+    content
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.split_whitespace();
+            let case = fields.next().unwrap().to_string();
+            let values = fields.map(|x| x.parse::<f64>().unwrap()).collect();
+            (case, values)
+        })
+        .collect()
+}
+
+/// Compare evaluated cases to published energies rounded to 1e-8 Hartree
+fn check_published(cases: &BTreeMap<String, Vec<f64>>, failures: &mut Vec<String>) {
+    for (case, expected) in read_entries(PUBLISHED_FILE) {
+        match cases.get(&case) {
+            None => failures.push(format!("{}: published energy of unknown case", case)),
+            Some(values) if (values[0] - expected[0]).abs() > ROUNDED_ENERGY_TOL => {
+                failures.push(format!(
+                    "{}: energy {:.10e} (published {:.8e})",
+                    case, values[0], expected[0]
+                ))
+            }
+            Some(_) => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reference() {
+        let mut failures = vec![];
+        let cases = evaluate_cases()
+            .into_iter()
+            .filter_map(|(case, values)| match values {
+                Ok(values) => Some((case, values)),
+                Err(message) => {
+                    failures.push(format!("{}: {}", case, message));
+                    None
+                }
+            })
+            .collect::<BTreeMap<_, _>>();
+        let nmethods = DAMPINGS.iter().map(|(_, x)| x.len()).sum::<usize>();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+        assert_eq!(
+            cases.len(),
+            SYSTEMS.len() * (2 * nmethods + GCP_METHODS.len())
+        );
+        check_published(&cases, &mut failures);
+
+        if std::env::var_os("DFTD3_BLESS").is_some() {
+            assert!(
+                failures.is_empty(),
+                "Library disagrees with published energies, not blessing:\n{}",
+                failures.join("\n")
+            );
+            let mut content = format!(
+                "# reference data of tests/test_reference.rs (s-dftd3 API version {})\n",
+                get_api_version()
+            );
+            for (case, values) in &cases {
+                content += &format!("{} {}\n", case, flatten(values));
+            }
+            std::fs::write(REFERENCE_FILE, content).unwrap();
+            return;
+        }
+
+        let reference = read_entries(REFERENCE_FILE);
+        for case in reference.keys().filter(|case| !cases.contains_key(*case)) {
+            failures.push(format!("{}: reference of unknown case", case));
+        }
+        for (case, values) in &cases {
+            let Some(expected) = reference.get(case) else {
+                failures.push(format!("{}: no reference data", case));
+                continue;
+            };
+            if expected.len() != values.len() {
+                failures.push(format!(
+                    "{}: expected {} values, got {}",
+                    case,
+                    expected.len(),
+                    values.len()
+                ));
+                continue;
+            }
+            let energy_diff = (values[0] - expected[0]).abs();
+            let gradient_diff = values[1..]
+                .iter()
+                .zip(&expected[1..])
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f64::max);
+            if energy_diff > ENERGY_TOL || gradient_diff > GRADIENT_TOL {
+                failures.push(format!(
+                    "{}: energy {:.12e} (reference {:.12e}), max derivative deviation {:.3e}",
+                    case, values[0], expected[0], gradient_diff
+                ));
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}